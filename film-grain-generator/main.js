@@ -1,14 +1,25 @@
 import { invoke } from '@tauri-apps/api/core';
+import { getCurrentWebview } from '@tauri-apps/api/webview';
+import { open, save } from '@tauri-apps/plugin-dialog';
+
+const OUTPUT_FORMATS = {
+    png: { name: 'PNG', extensions: ['png'] },
+    jpeg: { name: 'JPEG', extensions: ['jpg', 'jpeg'] },
+    tiff: { name: 'TIFF', extensions: ['tif', 'tiff'] },
+    webp: { name: 'WebP', extensions: ['webp'] },
+    exr: { name: 'OpenEXR', extensions: ['exr'] }
+};
 
 class FilmGrainGenerator {
     constructor() {
         this.canvas = document.getElementById('grainCanvas');
         this.ctx = this.canvas.getContext('2d');
-        this.currentImageData = null;
-        this.uploadedImage = null;
+        this.currentRenderId = null;
+        this.uploadedImageId = null;
         this.uploadedImageWidth = 0;
         this.uploadedImageHeight = 0;
         this.updateTimeout = null;
+        this.lastSeed = null;
         
         this.initializeControls();
     }
@@ -18,9 +29,11 @@ class FilmGrainGenerator {
             // Get categorized film stocks from backend
             const result = await invoke('get_categorized_film_stocks');
             const filmStockSelect = document.getElementById('filmStock');
+            const blendStockSelect = document.getElementById('blendStock');
             
             // Clear existing options
             filmStockSelect.innerHTML = '';
+            blendStockSelect.innerHTML = '<option value="">None</option>';
             
             // Add categorized film stocks with optgroups
             Object.keys(result).forEach(category => {
@@ -40,6 +53,7 @@ class FilmGrainGenerator {
                 });
                 
                 filmStockSelect.appendChild(optgroup);
+                blendStockSelect.appendChild(optgroup.cloneNode(true));
             });
             
             // Skip GPU info loading for now to avoid overhead
@@ -75,8 +89,51 @@ class FilmGrainGenerator {
         }
     }
     
-    async updateFilmInfo() {
+    async loadFilmFormats() {
+        try {
+            this.filmFormats = await invoke('get_film_formats');
+            const select = document.getElementById('filmFormat');
+            this.filmFormats.forEach(format => {
+                const option = document.createElement('option');
+                option.value = format.name;
+                option.textContent = `${format.name} (${format.gate_width_mm}×${format.gate_height_mm} mm)`;
+                select.appendChild(option);
+            });
+        } catch (error) {
+            console.error('Failed to load film formats:', error);
+            this.filmFormats = [];
+        }
+    }
+    
+    // Shows how big a full-frame scan would be, and how much of it the canvas covers
+    updateScanInfo() {
+        const info = document.getElementById('scanInfo');
+        const format = this.filmFormats?.find(f => f.name === document.getElementById('filmFormat').value);
+        const dpi = parseFloat(document.getElementById('scanDpi').value);
+        
+        if (!format) {
+            info.textContent = 'Grain sized in canvas pixels';
+        } else if (!dpi) {
+            info.textContent = 'Whole frame fits the canvas';
+        } else {
+            const width = Math.round(format.gate_width_mm / 25.4 * dpi);
+            const height = Math.round(format.gate_height_mm / 25.4 * dpi);
+            info.textContent = `Full frame ${width}×${height} px - canvas shows a 100% crop`;
+        }
+    }
+    
+    // Blends use the backend's "<stock> + <stock> @ <weight>" name syntax
+    getSelectedFilmStock() {
         const filmStock = document.getElementById('filmStock').value;
+        const blendStock = document.getElementById('blendStock')?.value;
+        if (!blendStock || blendStock === filmStock) return filmStock;
+        
+        const weight = parseFloat(document.getElementById('blendAmount').value) / 100.0;
+        return `${filmStock} + ${blendStock} @ ${weight.toFixed(2)}`;
+    }
+    
+    async updateFilmInfo() {
+        const filmStock = this.getSelectedFilmStock();
         if (!filmStock) return;
         
         try {
@@ -103,6 +160,7 @@ class FilmGrainGenerator {
     async initializeControls() {
         // Load available film stocks dynamically
         await this.loadFilmStocks();
+        await this.loadFilmFormats();
         
         // Film stock selector
         const filmStock = document.getElementById('filmStock');
@@ -111,10 +169,17 @@ class FilmGrainGenerator {
             this.regenerateGrain();
         });
         
+        const blendStock = document.getElementById('blendStock');
+        blendStock.addEventListener('change', () => {
+            this.updateFilmInfo();
+            this.regenerateGrain();
+        });
+        
         // Sliders with real-time updates
         const sliders = [
             'grainIntensity', 'grainSize', 'contrast', 'grainDensity',
-            'canvasWidth', 'canvasHeight', 'filmAge'
+            'canvasWidth', 'canvasHeight', 'filmAge', 'blendAmount', 'pushPull', 'halationStrength',
+            'bloomStrength', 'gateWeave'
         ];
         
         
@@ -130,14 +195,20 @@ class FilmGrainGenerator {
                 'grainDensity': 1000,
                 'canvasWidth': 1024,
                 'canvasHeight': 1024,
-                'filmAge': 0
+                'filmAge': 0,
+                'blendAmount': 50,
+                'pushPull': 0,
+                'halationStrength': 100,
+                'bloomStrength': 0,
+                'gateWeave': 0
             };
             
             const updateDisplay = (value) => {
                 let displayValue = value;
                 if (sliderId === 'grainIntensity') {
                     displayValue = displayValue == '0' ? '0' : (displayValue > 0 ? '+' + displayValue : displayValue);
-                } else if (sliderId === 'contrast') {
+                } else if (sliderId === 'contrast' || sliderId === 'blendAmount' || sliderId === 'halationStrength' ||
+                           sliderId === 'bloomStrength' || sliderId === 'gateWeave') {
                     displayValue += '%';
                 } else if (sliderId === 'grainSize') {
                     displayValue += 'x';
@@ -145,6 +216,8 @@ class FilmGrainGenerator {
                     displayValue = (parseFloat(displayValue) / 1000.0).toFixed(1) + 'x';
                 } else if (sliderId === 'filmAge') {
                     displayValue = displayValue == '0' ? 'Fresh' : displayValue + 'y';
+                } else if (sliderId === 'pushPull') {
+                    displayValue = displayValue == '0' ? 'Box speed' : (displayValue > 0 ? 'Push +' + displayValue : 'Pull ' + displayValue);
                 }
                 valueDisplay.textContent = displayValue;
             };
@@ -253,15 +326,26 @@ class FilmGrainGenerator {
             this.regenerateGrain();
         });
         
+        // Film format and scan resolution
+        ['filmFormat', 'scanDpi'].forEach(id => {
+            document.getElementById(id).addEventListener('change', () => {
+                this.updateScanInfo();
+                this.regenerateGrain();
+            });
+        });
+        this.updateScanInfo();
+        
+        ['renderMode', 'grainColorSource', 'halationMode', 'developer', 'agitation', 'shutterTime'].forEach(id => {
+            document.getElementById(id).addEventListener('change', () => {
+                this.regenerateGrain();
+            });
+        });
+        
         // Buttons
         document.getElementById('uploadBtn').addEventListener('click', () => {
             this.uploadImage();
         });
 
-        document.getElementById('imageUpload').addEventListener('change', (e) => {
-            this.handleImageUpload(e);
-        });
-
         document.getElementById('regenerateBtn').addEventListener('click', () => {
             this.regenerateGrain();
         });
@@ -274,10 +358,195 @@ class FilmGrainGenerator {
             this.saveCompositeImage();
         });
 
+        document.getElementById('outputFormat').addEventListener('change', () => {
+            this.updateOutputControls();
+        });
+
+        document.getElementById('jpegQuality').addEventListener('input', (e) => {
+            document.getElementById('jpegQualityValue').textContent = e.target.value;
+        });
+
+        ['grainSeed', 'frameIndex'].forEach(id => {
+            document.getElementById(id).addEventListener('change', () => {
+                this.regenerateGrain();
+            });
+        });
+
+        // Presets
+        document.getElementById('loadPresetBtn').addEventListener('click', () => {
+            this.loadPreset(document.getElementById('presetSelect').value);
+        });
+
+        document.getElementById('savePresetBtn').addEventListener('click', () => {
+            this.savePreset();
+        });
+
+        document.getElementById('importPresetBtn').addEventListener('click', () => {
+            document.getElementById('presetImport').click();
+        });
+
+        document.getElementById('presetImport').addEventListener('change', (e) => {
+            this.importPresetFile(e);
+        });
+
+        this.refreshPresetList();
+
+        // Drop a previously exported image on the window to restore the recipe it was made with
+        getCurrentWebview().onDragDropEvent((event) => {
+            if (event.payload.type === 'drop' && event.payload.paths.length > 0) {
+                this.restoreRecipeFromImage(event.payload.paths[0]);
+            }
+        });
+
         // Tab functionality
         this.initializeTabs();
     }
     
+    async refreshPresetList() {
+        try {
+            const presets = await invoke('list_presets');
+            const presetSelect = document.getElementById('presetSelect');
+            presetSelect.innerHTML = '';
+            
+            if (presets.length === 0) {
+                presetSelect.innerHTML = '<option value="">No saved presets</option>';
+                return;
+            }
+            
+            presets.forEach(preset => {
+                const option = document.createElement('option');
+                option.value = preset.name;
+                option.textContent = `${preset.name} (${preset.film_stock})`;
+                option.title = preset.description || preset.path;
+                presetSelect.appendChild(option);
+            });
+        } catch (error) {
+            console.error('Failed to list presets:', error);
+        }
+    }
+    
+    async savePreset() {
+        const name = document.getElementById('presetName').value.trim();
+        if (!name) {
+            alert('Please enter a preset name');
+            return;
+        }
+        
+        // Store the seed of the grain currently on screen so the preset reproduces it exactly
+        const params = this.getGrainParameters();
+        if (params.seed === null) params.seed = this.lastSeed;
+        
+        try {
+            const path = await invoke('save_preset', {
+                preset: {
+                    name,
+                    description: document.getElementById('presetDescription').value.trim(),
+                    params
+                }
+            });
+            await this.refreshPresetList();
+            document.getElementById('presetSelect').value = name;
+            alert(`Preset saved to ${path}`);
+        } catch (error) {
+            console.error('Error saving preset:', error);
+            alert('Error saving preset: ' + error);
+        }
+    }
+    
+    async loadPreset(name) {
+        if (!name) return;
+        
+        try {
+            const preset = await invoke('load_preset', { name });
+            this.applyPreset(preset);
+        } catch (error) {
+            console.error('Error loading preset:', error);
+            alert('Error loading preset: ' + error);
+        }
+    }
+    
+    async importPresetFile(event) {
+        const file = event.target.files[0];
+        if (!file) return;
+        event.target.value = '';
+        
+        try {
+            const preset = JSON.parse(await file.text());
+            await invoke('save_preset', { preset });
+            await this.refreshPresetList();
+            this.applyPreset(preset);
+        } catch (error) {
+            console.error('Error importing preset:', error);
+            alert('Error importing preset: ' + error);
+        }
+    }
+    
+    async restoreRecipeFromImage(path) {
+        try {
+            const preset = await invoke('read_image_recipe', { path });
+            this.applyPreset(preset);
+            console.log('Restored grain recipe from', path);
+        } catch (error) {
+            console.error('Error reading grain recipe:', error);
+            alert('Could not restore grain settings: ' + error);
+        }
+    }
+    
+    applyPreset(preset) {
+        const params = preset.params;
+        
+        // Split blended stock names back into the two selectors
+        const blend = params.film_stock.match(/^(.+) \+ (.+) @ ([\d.]+)$/);
+        document.getElementById('filmStock').value = blend ? blend[1] : params.film_stock;
+        document.getElementById('blendStock').value = blend ? blend[2] : '';
+        if (blend) document.getElementById('blendAmount').value = Math.round(parseFloat(blend[3]) * 100);
+        
+        const sliderValues = {
+            'grainIntensity': params.exposure_compensation,
+            'grainSize': params.size_multiplier,
+            'contrast': params.contrast,
+            'grainDensity': params.grain_density,
+            'canvasWidth': params.width,
+            'canvasHeight': params.height,
+            'filmAge': params.film_age_years ?? 0,
+            'pushPull': params.push_pull_stops ?? 0,
+            'halationStrength': Math.round((params.halation_strength || 1) * 100),
+            'bloomStrength': Math.round((params.bloom_strength ?? 0) * 100),
+            'gateWeave': Math.round((params.gate_weave ?? 0) * 100),
+            'blendAmount': document.getElementById('blendAmount').value
+        };
+        Object.entries(sliderValues).forEach(([sliderId, value]) => {
+            const slider = document.getElementById(sliderId);
+            slider.value = value;
+            if (sliderId === 'canvasWidth' || sliderId === 'canvasHeight') {
+                document.getElementById(sliderId + 'Value').textContent = value;
+            } else {
+                // Let the slider's own handler refresh its value display
+                slider.dispatchEvent(new Event('input'));
+            }
+        });
+        clearTimeout(this.updateTimeout);
+        
+        document.getElementById('storageType').value = String(params.storage_temp ?? 20);
+        document.getElementById('filmFormat').value = params.film_format ?? '';
+        document.getElementById('scanDpi').value = params.scan_dpi ? String(params.scan_dpi) : '';
+        this.updateScanInfo();
+        document.getElementById('renderMode').value = params.render_mode ?? 'stamped';
+        document.getElementById('grainColorSource').value = params.grain_color_source ?? 'stock';
+        document.getElementById('halationMode').value =
+            params.halation_strength == null ? 'auto' : (params.halation_strength > 0 ? 'all' : 'off');
+        document.getElementById('developer').value = params.developer ?? 'standard';
+        document.getElementById('agitation').value = params.agitation ?? 'normal';
+        document.getElementById('shutterTime').value = params.shutter_time ? String(params.shutter_time) : '';
+        document.getElementById('grainSeed').value = params.seed ?? '';
+        document.getElementById('frameIndex').value = params.frame_index ?? 0;
+        document.getElementById('presetName').value = preset.name || '';
+        document.getElementById('presetDescription').value = preset.description || '';
+        
+        this.updateFilmInfo();
+        this.updateCanvasSize();
+    }
+    
     initializeTabs() {
         const tabButtons = document.querySelectorAll('.tab-button');
         const tabPanels = document.querySelectorAll('.tab-panel');
@@ -371,8 +640,8 @@ class FilmGrainGenerator {
             // Call Rust backend for high-performance grain generation
             const result = await invoke('generate_grain', { params });
             
-            // Convert the raw data to ImageData and display
-            this.displayGrainResult(result);
+            // Fetch the cached render as a PNG and display it
+            await this.displayGrainResult(result);
             
             // Update performance info
             this.updatePerformanceInfo(result);
@@ -434,7 +703,7 @@ class FilmGrainGenerator {
     
     getGrainParameters() {
         return {
-            film_stock: document.getElementById('filmStock').value,
+            film_stock: this.getSelectedFilmStock(),
             exposure_compensation: parseFloat(document.getElementById('grainIntensity').value),
             size_multiplier: parseFloat(document.getElementById('grainSize').value),
             contrast: parseFloat(document.getElementById('contrast').value),
@@ -443,49 +712,63 @@ class FilmGrainGenerator {
             height: parseInt(document.getElementById('canvasHeight').value),
             background: 'transparent',
             film_age_years: parseFloat(document.getElementById('filmAge')?.value || 0),
-            storage_temp: parseFloat(document.getElementById('storageType')?.value || 20)
+            storage_temp: parseFloat(document.getElementById('storageType')?.value || 20),
+            film_format: document.getElementById('filmFormat').value || null,
+            scan_dpi: parseFloat(document.getElementById('scanDpi').value) || null,
+            render_mode: document.getElementById('renderMode').value,
+            grain_color_source: document.getElementById('grainColorSource').value,
+            halation_strength: this.getHalationStrength(),
+            bloom_strength: parseFloat(document.getElementById('bloomStrength').value) / 100 || null,
+            gate_weave: parseFloat(document.getElementById('gateWeave').value) / 100 || null,
+            frame_index: parseInt(document.getElementById('frameIndex').value) || null,
+            push_pull_stops: parseFloat(document.getElementById('pushPull').value),
+            developer: document.getElementById('developer').value,
+            agitation: document.getElementById('agitation').value,
+            shutter_time: parseFloat(document.getElementById('shutterTime').value) || null,
+            seed: this.getSeed()
         };
     }
     
-    displayGrainResult(result) {
-        const { data, width, height } = result;
+    // Without a strength only remjet-free stocks glow; any strength applies to every stock
+    getHalationStrength() {
+        const mode = document.getElementById('halationMode').value;
+        if (mode === 'auto') return null;
+        return mode === 'off' ? 0 : parseFloat(document.getElementById('halationStrength').value) / 100;
+    }
+    
+    getSeed() {
+        const value = document.getElementById('grainSeed')?.value;
+        return value ? parseInt(value) : null;
+    }
+    
+    async displayGrainResult(result) {
+        const { render_id, width, height } = result;
+        
+        // The render stays in the backend; only an encoded preview comes over IPC
+        const png = await invoke('get_render_preview', { renderId: render_id });
+        const grain = await createImageBitmap(new Blob([png], { type: 'image/png' }));
         
         // Set canvas size
         this.canvas.width = width;
         this.canvas.height = height;
         
-        // Create ImageData from the raw RGBA data
-        const imageData = new ImageData(new Uint8ClampedArray(data), width, height);
-        
         // If there's an uploaded image, use it as background instead of black
-        if (this.uploadedImage && this.uploadedImageElement) {
+        if (this.uploadedImageId !== null && this.uploadedImageElement) {
             console.log('Drawing uploaded image as background');
-            
-            // Draw uploaded image as background
             this.ctx.drawImage(this.uploadedImageElement, 0, 0, this.canvas.width, this.canvas.height);
-            
-            // Create temporary canvas for grain to preserve transparency
-            const tempCanvas = document.createElement('canvas');
-            tempCanvas.width = width;
-            tempCanvas.height = height;
-            const tempCtx = tempCanvas.getContext('2d');
-            tempCtx.putImageData(imageData, 0, 0);
-            
-            // Draw grain on top preserving its transparency
-            this.ctx.drawImage(tempCanvas, 0, 0);
-            
-            console.log('Grain drawn on top of uploaded image');
         } else {
             // No uploaded image, use black background as before
             this.ctx.fillStyle = '#000000';
             this.ctx.fillRect(0, 0, this.canvas.width, this.canvas.height);
-            
-            // Draw the transparent grain on top
-            this.ctx.putImageData(imageData, 0, 0);
         }
         
-        // Store original transparent data for saving (unchanged)
-        this.currentImageData = data;
+        // Draw the transparent grain on top
+        this.ctx.drawImage(grain, 0, 0);
+        grain.close();
+        
+        // Saves refer to the cached render (unchanged transparent grain)
+        this.currentRenderId = render_id;
+        this.lastSeed = result.seed;
     }
     
     updatePerformanceInfo(result) {
@@ -499,131 +782,161 @@ class FilmGrainGenerator {
         info.innerHTML += ` | ${perfIndicator}`;
     }
     
-    uploadImage() {
-        document.getElementById('imageUpload').click();
-    }
-
-    async handleImageUpload(event) {
-        const file = event.target.files[0];
-        if (!file) return;
+    // Photos are opened by path and decoded once in the backend; the webview only
+    // receives a thumbnail to draw behind the grain
+    async uploadImage() {
+        const path = await open({
+            multiple: false,
+            filters: [{ name: 'Images', extensions: ['png', 'jpg', 'jpeg', 'tif', 'tiff', 'webp', 'bmp', 'exr'] }]
+        });
+        if (!path) return;
 
         try {
             // Show loading for image upload
             this.showLoadingBar('Loading image...');
             
-            // Simple approach - load image directly in browser
-            const img = new Image();
-            img.onload = () => {
-                console.log('Image loaded successfully:', file.name);
-                
-                // Store the image element for later use
-                this.uploadedImageElement = img;
-                this.uploadedImage = true; // Flag to indicate image is loaded
-                
-                // Store image dimensions and auto-resize canvas
-                this.uploadedImageWidth = img.naturalWidth;
-                this.uploadedImageHeight = img.naturalHeight;
-                this.autoResizeCanvasToImage(img.naturalWidth, img.naturalHeight);
-                
-                // Update canvas to show the image immediately
-                const params = this.getGrainParameters();
-                this.canvas.width = params.width;
-                this.canvas.height = params.height;
-                
-                // Draw the uploaded image as background
-                this.ctx.clearRect(0, 0, this.canvas.width, this.canvas.height);
-                this.ctx.drawImage(img, 0, 0, this.canvas.width, this.canvas.height);
-                
-                // Show the composite save button
-                document.getElementById('saveCompositeBtn').style.display = 'inline-block';
-                
-                // Update button text to show image is loaded
-                document.getElementById('uploadBtn').textContent = `📁 ${file.name}`;
-                
-                console.log('Image displayed on canvas');
-                
-                // Hide loading bar
-                this.hideLoadingBar();
-                
-                // Regenerate grain on top of the image
-                this.regenerateGrain();
-            };
+            const opened = await invoke('open_user_image', { path });
+            const thumbnail = await invoke('get_image_thumbnail', { imageId: opened.image_id });
+            const img = await createImageBitmap(new Blob([thumbnail], { type: 'image/png' }));
+            console.log('Image loaded successfully:', opened.file_name);
             
-            img.onerror = () => {
-                console.error('Failed to load image');
-                this.hideLoadingBar();
-                alert('Failed to load image');
-            };
+            // Store the thumbnail for drawing and the ID for compositing
+            this.uploadedImageElement?.close?.();
+            this.uploadedImageElement = img;
+            this.uploadedImageId = opened.image_id;
             
-            // Load image from file
-            const reader = new FileReader();
-            reader.onload = (e) => {
-                img.src = e.target.result;
-            };
-            reader.readAsDataURL(file);
+            // Store full-resolution dimensions and auto-resize canvas
+            this.uploadedImageWidth = opened.width;
+            this.uploadedImageHeight = opened.height;
+            this.autoResizeCanvasToImage(opened.width, opened.height);
+            
+            // Update canvas to show the image immediately
+            const params = this.getGrainParameters();
+            this.canvas.width = params.width;
+            this.canvas.height = params.height;
+            
+            // Draw the uploaded image as background
+            this.ctx.clearRect(0, 0, this.canvas.width, this.canvas.height);
+            this.ctx.drawImage(img, 0, 0, this.canvas.width, this.canvas.height);
+            
+            // Show the composite save button
+            document.getElementById('saveCompositeBtn').style.display = 'inline-block';
+            
+            // Update button text to show image is loaded
+            document.getElementById('uploadBtn').textContent = `📁 ${opened.file_name}`;
+            
+            console.log('Image displayed on canvas');
+            
+            // Hide loading bar
+            this.hideLoadingBar();
+            
+            // Regenerate grain on top of the image
+            this.regenerateGrain();
             
         } catch (error) {
             console.error('Error loading image:', error);
+            this.hideLoadingBar();
             alert('Error loading image: ' + error);
         }
     }
 
+    updateOutputControls() {
+        const format = document.getElementById('outputFormat').value;
+        document.getElementById('jpegQualityGroup').style.display = format === 'jpeg' ? 'block' : 'none';
+        document.getElementById('tiffCompressionGroup').style.display = format === 'tiff' ? 'block' : 'none';
+    }
+
+    getOutputOptions() {
+        return {
+            format: document.getElementById('outputFormat').value,
+            jpeg_quality: parseInt(document.getElementById('jpegQuality').value),
+            tiff_compression: document.getElementById('tiffCompression').value,
+            overwrite: document.getElementById('overwritePolicy').value
+        };
+    }
+
+    // Asks where to save; returns null if the user cancels the dialog
+    async chooseOutputPath(prefix, params, options) {
+        const format = OUTPUT_FORMATS[options.format];
+        const timestamp = new Date().toISOString().replace(/[:.]/g, '-');
+        const filename = `${prefix}_${params.film_stock.replace(/[^A-Za-z0-9.-]+/g, '_')}_${timestamp}.${format.extensions[0]}`;
+
+        return await save({
+            defaultPath: filename,
+            filters: [format]
+        });
+    }
+
+    // Runs a save command; if the file exists the user is asked before it gets replaced
+    async invokeSave(command, args) {
+        try {
+            return await invoke(command, args);
+        } catch (error) {
+            if (error?.kind !== 'alreadyExists' || !confirm(`${error.message}. Replace it?`)) {
+                throw error;
+            }
+            return await invoke(command, { ...args, options: { ...args.options, overwrite: 'replace' } });
+        }
+    }
+
     async saveImage() {
-        if (!this.currentImageData) {
+        if (this.currentRenderId === null) {
             alert('No grain image to save');
             return;
         }
         
         try {
             const params = this.getGrainParameters();
+            const options = this.getOutputOptions();
             
-            // Generate filename with timestamp and parameters
-            const timestamp = new Date().toISOString().replace(/[:.]/g, '-');
-            const filename = `grain_${params.film_stock.replace(/\s+/g, '_')}_${timestamp}.png`;
+            const path = await this.chooseOutputPath('grain', params, options);
+            if (!path) {
+                return;
+            }
             
-            // Save to Downloads folder (simplified approach)
-            await invoke('save_grain_image', {
-                data: this.currentImageData,
-                width: params.width,
-                height: params.height,
-                path: filename
+            const savedPath = await this.invokeSave('save_grain_image', {
+                renderId: this.currentRenderId,
+                path,
+                options
             });
             
-            alert(`Grain image saved as ${filename}!`);
+            alert(`Grain image saved as ${savedPath}!`);
             
         } catch (error) {
             console.error('Error saving image:', error);
-            alert('Error saving image: ' + error);
+            alert('Error saving image: ' + (error?.message ?? error));
         }
     }
 
     async saveCompositeImage() {
-        if (!this.currentImageData || !this.uploadedImage) {
+        if (this.currentRenderId === null || this.uploadedImageId === null) {
             alert('Please upload an image and generate grain first');
             return;
         }
         
         try {
             const params = this.getGrainParameters();
+            const options = this.getOutputOptions();
             
-            // Generate filename with timestamp and parameters
-            const timestamp = new Date().toISOString().replace(/[:.]/g, '-');
-            const filename = `composite_${params.film_stock.replace(/\s+/g, '_')}_${timestamp}.png`;
+            const path = await this.chooseOutputPath('composite', params, options);
+            if (!path) {
+                return;
+            }
             
             // Composite image with grain in Rust backend
-            await invoke('save_composite_image', {
-                grainData: this.currentImageData,
-                grainWidth: params.width,
-                grainHeight: params.height,
-                baseImageData: this.uploadedImage,
-                path: filename
+            const message = await this.invokeSave('save_composite_image', {
+                renderId: this.currentRenderId,
+                imageId: this.uploadedImageId,
+                path,
+                options,
+                cropToFormat: document.getElementById('cropToFormat').value === 'true'
             });
             
-            alert(`Composite image saved as ${filename}!`);
+            alert(message);
             
         } catch (error) {
             console.error('Error saving composite image:', error);
-            alert('Error saving composite image: ' + error);
+            alert('Error saving composite image: ' + (error?.message ?? error));
         }
     }
 }
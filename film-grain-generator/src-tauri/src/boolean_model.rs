@@ -0,0 +1,512 @@
+use image::RgbaImage;
+use rayon::prelude::*;
+use wide::*;
+
+use crate::characteristic_curve::{log_exposure_of_value, CharacteristicCurve};
+use crate::color_response::luminance;
+use crate::development::Development;
+use crate::dye_layers;
+use crate::exposure::{Exposure, ExposureResponse};
+use crate::{film_format, motion_effects, split_mix64, tiled, FilmStock, GrainParams, StockData};
+
+// Physically based alternative to stamping translucent grains: the Boolean model of Newson
+// et al., "A Stochastic Film Grain Model for Resolution-Independent Rendering" (2017).
+// Grain centres form a Poisson process, every grain is an opaque disc, and a pixel's coverage
+// is the chance that a point near it (Gaussian weighted) lies under at least one disc.
+//
+// The image's tone sets the process through the stock's characteristic curve: scene value
+// -> log exposure -> developed density D, and Nutting's relation for opaque grains
+// (D = coverage factor · log10 e) gives the intensity. Emulsions packing more grains than the
+// reference, or a raised grain density, cover more at every tone. Dense tones are buried under
+// overlapping grains, thin ones show isolated specks. Each pixel keeps its tone and gains the
+// difference between its coverage and the tone's expected coverage, scaled up where the toe
+// or shoulder squeezes tones together, so grain shows where the curve says it should.
+const TILE_SIZE: usize = 64;
+const LANES: usize = 8;
+const SAMPLE_GROUPS: usize = 4; // Monte Carlo points per pixel, in SIMD groups
+const SAMPLES: usize = SAMPLE_GROUPS * LANES;
+const FILTER_SIGMA: f32 = 0.4; // Standard deviation, in pixels, of each pixel's filter
+const MAX_GRAINS_PER_CELL: u32 = 256;
+// Grains per mm² at which the grains alone build the curve's density; a stock's
+// density_per_mm2 relative to this crowds in more grains at every tone
+const REFERENCE_GRAINS_PER_MM2: f32 = 800_000.0;
+// The preview layer has no photo to follow, so it shows the grain of a flat mid-grey
+const PREVIEW_TONE: u8 = 128;
+
+#[derive(Debug, Clone, Copy)]
+struct Disc {
+    x: f32,
+    y: f32,
+    radius_squared: f32,
+}
+
+pub struct BooleanModel {
+    // Log-normal radius distribution, in pixels, scaled for each tone
+    radius_log_sigma: f32,
+    radius_log_mean: [f32; 256],
+    max_radius: [f32; 256],
+    // Farthest a sample point gets from its pixel centre, and a grain centre from a pixel
+    // centre while still covering one of its samples
+    sample_spread: f32,
+    reach: f32,
+    // Per 8-bit tone
+    intensity: [f32; 256],         // Grains per pixel cell
+    poisson_zero: [f32; 256],      // e^-intensity, the chance a cell has no grain
+    expected_coverage: [f32; 256], // 1 - 10^-D
+    grain_gain: [f32; 256],        // 8-bit levels per unit of coverage above or below that
+    sample_x: [f32x8; SAMPLE_GROUPS],
+    sample_y: [f32x8; SAMPLE_GROUPS],
+}
+
+impl BooleanModel {
+    // Grain radii from the stock's SizeMetrics at the film scale the stamped renderer uses,
+    // so both modes show grain of the same size at mid-grey
+    pub fn new(stock: &FilmStock, exposure_response: &ExposureResponse, params: &GrainParams) -> Result<Self, String> {
+        let scale = film_format::GrainScale::for_params(params, stock, params.grain_density as f32 / 1000.0)?;
+        let metrics = &stock.size_metrics;
+        // Development grows the grain and steepens the curve, and the steeper print shows the
+        // grain harder. The model's grains are independent and hard-edged, so clumping and
+        // edge effects don't apply
+        let development = Development::for_params(params, stock);
+        let exposure = Exposure::for_params(params, exposure_response);
+        let pixels_per_micron = scale.pixels_per_micron * params.size_multiplier * development.grain_size * exposure.grain_size;
+        let mean_radius = metrics.avg_size_um * 0.5 * pixels_per_micron;
+        let max_radius = metrics.max_size_um * 0.5 * pixels_per_micron;
+        let curve = stock.characteristic_curve.developed(&development);
+        let crowding = metrics.density_per_mm2 as f32 / REFERENCE_GRAINS_PER_MM2 * params.grain_density as f32 / 1000.0;
+        let mut model = Self::from_grain(mean_radius, metrics.size_variation_coeff * development.size_spread, max_radius, crowding, &curve);
+        let contrast = params.contrast / 100.0 * development.contrast * exposure.grain_visibility;
+        model.grain_gain = model.grain_gain.map(|gain| gain * contrast);
+        Ok(model)
+    }
+
+    // `crowding` multiplies the grain count, and with it the density grains build, at every tone
+    fn from_grain(mean_radius: f32, variation_coeff: f32, max_radius: f32, crowding: f32, curve: &CharacteristicCurve) -> Self {
+        let mean_radius = mean_radius.max(0.05);
+        let max_radius = max_radius.max(mean_radius);
+        // Mean radius and coefficient of variation as the log-normal's parameters
+        let spread = (1.0 + variation_coeff * variation_coeff).ln();
+        let (radius_log_mean, radius_log_sigma) = (mean_radius.ln() - 0.5 * spread, spread.sqrt());
+
+        // Mean grain area π E[r²] with radii capped at max_radius, integrated over the normal
+        let grain_area: f32 = (-600..=600)
+            .map(|step| {
+                let z = step as f32 * 0.01;
+                let radius = (radius_log_mean + radius_log_sigma * z).exp().min(max_radius);
+                radius * radius * (-0.5 * z * z).exp() * 0.01 / (std::f32::consts::TAU).sqrt()
+            })
+            .sum::<f32>()
+            * std::f32::consts::PI;
+
+        // Each tone's place on the curve, and the grain size developed there
+        let log_exposure: [f32; 256] = std::array::from_fn(|value| log_exposure_of_value(value as u8));
+        let curve_density = log_exposure.map(|log_exposure| curve.density(log_exposure));
+        let density = curve_density.map(|density| density * crowding.max(0.0));
+        let radius_scale = curve_density.map(|density| curve.grain_size_scale(density));
+
+        // Coverage of a Boolean model is 1 - e^(-intensity · π E[r²]) and density is
+        // -log10(1 - coverage), so intensity = D · ln 10 / π E[r²]
+        let intensity: [f32; 256] = std::array::from_fn(|value| {
+            density[value] * std::f32::consts::LN_10 / (grain_area * radius_scale[value] * radius_scale[value])
+        });
+        let grain_gain = log_exposure.map(|log_exposure| 255.0 * curve.grain_gain(log_exposure));
+        let max_radius = radius_scale.map(|scale| max_radius * scale);
+        let widest = max_radius.iter().copied().fold(0.0, f32::max);
+
+        // Fixed Gaussian sunflower of sample offsets: even coverage without per-pixel noise
+        let golden_angle = std::f32::consts::PI * (3.0 - 5f32.sqrt());
+        let offsets: [(f32, f32); SAMPLES] = std::array::from_fn(|i| {
+            let distance = FILTER_SIGMA * (-2.0 * (1.0 - (i as f32 + 0.5) / SAMPLES as f32).ln()).sqrt();
+            let angle = i as f32 * golden_angle;
+            (distance * angle.cos(), distance * angle.sin())
+        });
+        let sample_spread = offsets.iter().map(|(x, y)| x.hypot(*y)).fold(0.0, f32::max);
+        let group = |axis: fn(&(f32, f32)) -> f32| -> [f32x8; SAMPLE_GROUPS] {
+            std::array::from_fn(|g| f32x8::new(std::array::from_fn(|lane| axis(&offsets[g * LANES + lane]))))
+        };
+
+        Self {
+            radius_log_sigma,
+            radius_log_mean: radius_scale.map(|scale| radius_log_mean + scale.ln()),
+            max_radius,
+            sample_spread,
+            reach: widest + sample_spread,
+            intensity,
+            poisson_zero: intensity.map(|intensity| (-intensity).exp()),
+            expected_coverage: density.map(|density| 1.0 - 10f32.powf(-density)),
+            grain_gain,
+            sample_x: group(|offset| offset.0),
+            sample_y: group(|offset| offset.1),
+        }
+    }
+
+    // Grains centred in pixel cell (x, y) exposed to `tone`. A cell draws the same numbers
+    // whichever tile asks, and a brighter tone keeps its grains and adds more.
+    fn cell_grains(&self, seed: u64, x: i32, y: i32, tone: u8, grains: &mut Vec<Disc>) {
+        let intensity = self.intensity[tone as usize];
+        if intensity <= 0.0 {
+            return;
+        }
+        let mut random = CellRandom::new(seed, x, y);
+
+        // Poisson count by inverting its CDF with a single uniform
+        let chance = random.next();
+        let mut probability = self.poisson_zero[tone as usize];
+        let mut cumulative = probability;
+        let mut count = 0;
+        while chance > cumulative && count < MAX_GRAINS_PER_CELL {
+            count += 1;
+            probability *= intensity / count as f32;
+            cumulative += probability;
+        }
+
+        for _ in 0..count {
+            let (grain_x, grain_y) = (x as f32 + random.next(), y as f32 + random.next());
+            let normal = (-2.0 * (1.0 - random.next()).ln()).sqrt() * (std::f32::consts::TAU * random.next()).cos();
+            let radius = (self.radius_log_mean[tone as usize] + self.radius_log_sigma * normal).exp().min(self.max_radius[tone as usize]);
+            grains.push(Disc { x: grain_x, y: grain_y, radius_squared: radius * radius });
+        }
+    }
+
+    // Renders one tile's grain, as 8-bit levels to add to each pixel's tone, into its rows of
+    // a plane. `left`/`top` is the tile's canvas position; tone(x, y) is read clamped to the
+    // width×height canvas. Returns the number of grains centred in the tile.
+    fn render_tile<T>(&self, tone: &T, seed: u64, (width, height): (i32, i32), (left, top): (i32, i32), rows: Vec<&mut [i16]>) -> usize
+    where
+        T: Fn(i32, i32) -> u8 + Sync,
+    {
+        let tile_width = rows.first().map_or(0, |row| row.len()) as i32;
+        let tile_height = rows.len() as i32;
+        let margin = self.reach.ceil() as i32;
+        // Bins a few pixel cells wide, so one pixel looks through about 25 of them
+        let bin_size = ((self.reach / 2.0).ceil() as i32).max(1);
+        let (region_left, region_top) = (left - margin, top - margin);
+        let bins_across = (tile_width + 2 * margin + bin_size - 1) / bin_size;
+        let bins_down = (tile_height + 2 * margin + bin_size - 1) / bin_size;
+
+        // Generate bin by bin, so each bin's grains are one run of the list
+        let mut grains = Vec::new();
+        let mut bin_starts = Vec::with_capacity((bins_across * bins_down + 1) as usize);
+        let mut grain_count = 0;
+        for bin_y in 0..bins_down {
+            for bin_x in 0..bins_across {
+                bin_starts.push(grains.len());
+                let cell_left = region_left + bin_x * bin_size;
+                let cell_top = region_top + bin_y * bin_size;
+                let cell_right = (cell_left + bin_size).min(left + tile_width + margin);
+                let cell_bottom = (cell_top + bin_size).min(top + tile_height + margin);
+                for y in cell_top..cell_bottom {
+                    for x in cell_left..cell_right {
+                        let before = grains.len();
+                        self.cell_grains(seed, x, y, tone(x.clamp(0, width - 1), y.clamp(0, height - 1)), &mut grains);
+                        if x >= left && x < left + tile_width && y >= top && y < top + tile_height {
+                            grain_count += grains.len() - before;
+                        }
+                    }
+                }
+            }
+        }
+        bin_starts.push(grains.len());
+
+        let covered_reach = |grain: &Disc| {
+            let radius = grain.radius_squared.sqrt() + self.sample_spread;
+            radius * radius
+        };
+        let reaches: Vec<f32> = grains.iter().map(covered_reach).collect();
+
+        for (y, row) in rows.into_iter().enumerate() {
+            let center_y = (top + y as i32) as f32 + 0.5;
+            let bin_rows = self.bin_range(center_y, region_top, bin_size, bins_down);
+            for (x, value) in row.iter_mut().enumerate() {
+                let center_x = (left + x as i32) as f32 + 0.5;
+                let bin_columns = self.bin_range(center_x, region_left, bin_size, bins_across);
+                let sample_x = self.sample_x.map(|offsets| offsets + f32x8::splat(center_x));
+                let sample_y = self.sample_y.map(|offsets| offsets + f32x8::splat(center_y));
+                let mut covered = [f32x8::ZERO; SAMPLE_GROUPS];
+
+                'bins: for bin_y in bin_rows.clone() {
+                    let run = bin_y * bins_across as usize;
+                    for index in bin_starts[run + bin_columns.start]..bin_starts[run + bin_columns.end] {
+                        let grain = grains[index];
+                        let (dx, dy) = (grain.x - center_x, grain.y - center_y);
+                        if dx * dx + dy * dy >= reaches[index] {
+                            continue;
+                        }
+                        let (grain_x, grain_y) = (f32x8::splat(grain.x), f32x8::splat(grain.y));
+                        let radius_squared = f32x8::splat(grain.radius_squared);
+                        for group in 0..SAMPLE_GROUPS {
+                            let (dx, dy) = (sample_x[group] - grain_x, sample_y[group] - grain_y);
+                            covered[group] |= (dx * dx + dy * dy).cmp_lt(radius_squared);
+                        }
+                        if covered.iter().all(|mask| mask.all()) {
+                            break 'bins;
+                        }
+                    }
+                }
+
+                let hits: u32 = covered.iter().map(|mask| mask.move_mask().count_ones()).sum();
+                let pixel_tone = tone(left + x as i32, top + y as i32) as usize;
+                let coverage = hits as f32 / SAMPLES as f32;
+                *value = (self.grain_gain[pixel_tone] * (coverage - self.expected_coverage[pixel_tone])).round() as i16;
+            }
+        }
+        grain_count
+    }
+
+    // Bins whose cells can hold a grain covering samples of the pixel centred at `center`
+    fn bin_range(&self, center: f32, region_start: i32, bin_size: i32, bins: i32) -> std::ops::Range<usize> {
+        let first = ((center - self.reach).floor() as i32 - region_start) / bin_size;
+        let last = ((center + self.reach).floor() as i32 - region_start) / bin_size;
+        first.max(0) as usize..(last + 1).min(bins) as usize
+    }
+
+    // Renders the grain of rows top.. of a width×height canvas into `out`, one value per
+    // pixel and as many rows as it holds. Returns the number of grains centred in those rows.
+    fn render_plane<T>(&self, tone: &T, seed: u64, (width, height): (u32, u32), top: u32, out: &mut [i16]) -> usize
+    where
+        T: Fn(i32, i32) -> u8 + Sync,
+    {
+        let width = width as usize;
+        let columns = width.div_ceil(TILE_SIZE);
+        let tile_rows = (out.len() / width).div_ceil(TILE_SIZE);
+
+        // Hand each tile its own slices of the output rows, as the stamp rasteriser does
+        let mut tiles: Vec<Vec<&mut [i16]>> = (0..columns * tile_rows).map(|_| Vec::new()).collect();
+        for (y, row) in out.chunks_exact_mut(width).enumerate() {
+            for (tx, piece) in row.chunks_mut(TILE_SIZE).enumerate() {
+                tiles[y / TILE_SIZE * columns + tx].push(piece);
+            }
+        }
+
+        tiles
+            .into_par_iter()
+            .enumerate()
+            .map(|(index, rows)| {
+                let origin = ((index % columns * TILE_SIZE) as i32, (top as usize + index / columns * TILE_SIZE) as i32);
+                self.render_tile(tone, seed, (width as i32, height as i32), origin, rows)
+            })
+            .sum()
+    }
+}
+
+// Uniform numbers in [0, 1) for one pixel cell, keyed by the seed and the cell's position
+struct CellRandom {
+    state: u64,
+}
+
+impl CellRandom {
+    fn new(seed: u64, x: i32, y: i32) -> Self {
+        Self { state: split_mix64(seed ^ ((y as u32 as u64) << 32 | x as u32 as u64)) }
+    }
+
+    fn next(&mut self) -> f32 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        (split_mix64(self.state) >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+// The grain fields to render with their seeds: one per dye layer of a colour stock, each
+// at its layer's grain size and density, or a single field for black-and-white stocks
+fn load_fields(params: &GrainParams) -> Result<Vec<(BooleanModel, u64)>, String> {
+    let data = StockData::load(&params.film_stock)?;
+    let seed = motion_effects::grain_seed(params);
+    if data.stock.basic_info.film_type == "color" {
+        data.stock.dye_layers.layers().iter().enumerate()
+            .map(|(index, layer)| {
+                let mut layer_params = dye_layers::layer_params(params, layer, index);
+                // Each layer builds its channel's whole density on its own rather than a third
+                // of a shared one, so it keeps the stock's grain density scaled by its share
+                layer_params.grain_density = ((params.grain_density as f32 * layer.density_scale).round() as u32).max(1);
+                Ok((BooleanModel::new(&data.stock, &data.exposure, &layer_params)?, dye_layers::layer_seed(seed, index)))
+            })
+            .collect()
+    } else {
+        Ok(vec![(BooleanModel::new(&data.stock, &data.exposure, params)?, seed)])
+    }
+}
+
+// The overlay colour and alpha that turn the mid-grey into PREVIEW_TONE + grain in each
+// channel, with the least alpha that reaches all three: neutral grain is a black or white
+// overlay like a stamped layer, colour grain tints it
+fn preview_overlay(grain: [i16; 3]) -> [u8; 4] {
+    let grey = PREVIEW_TONE as f32;
+    let values = grain.map(|grain| (PREVIEW_TONE as i16 + grain).clamp(0, 255) as f32);
+    let alpha = values.iter()
+        .map(|&value| if value >= grey { (value - grey) / (255.0 - grey) } else { (grey - value) / grey })
+        .fold(0.0, f32::max);
+    if alpha == 0.0 {
+        return [255, 255, 255, 0];
+    }
+    let color = values.map(|value| (grey + (value - grey) / alpha).round().clamp(0.0, 255.0) as u8);
+    [color[0], color[1], color[2], (alpha * 255.0).round() as u8]
+}
+
+// The preview/export grain layer, band by band like the tiled renderer. Each pixel is the
+// overlay that turns a flat mid-grey into the model's render, so the layer composites like
+// stamped grain. Returns the number of grains generated.
+pub fn render_layer_bands<F>(params: &GrainParams, mut on_band: F) -> Result<usize, String>
+where
+    F: FnMut(&[u8]) -> Result<(), String>,
+{
+    let render_start = std::time::Instant::now();
+    let fields = load_fields(params)?;
+    let mut grain_count = 0;
+    let mut planes = vec![Vec::new(); fields.len()];
+    let mut band = Vec::new();
+    for top in (0..params.height).step_by(tiled::TILE_SIZE as usize) {
+        let band_height = tiled::TILE_SIZE.min(params.height - top);
+        for ((model, seed), plane) in fields.iter().zip(&mut planes) {
+            plane.resize(params.width as usize * band_height as usize, 0);
+            grain_count += model.render_plane(&|_, _| PREVIEW_TONE, *seed, (params.width, params.height), top, plane);
+        }
+
+        // A single field is neutral grain in every channel
+        let channel_plane = |channel: usize| &planes[channel.min(planes.len() - 1)];
+        band.clear();
+        band.extend((0..planes[0].len()).flat_map(|i| preview_overlay([channel_plane(0)[i], channel_plane(1)[i], channel_plane(2)[i]])));
+        on_band(&band)?;
+    }
+
+    println!("⏱️ Boolean model: {}ms for {} grains", render_start.elapsed().as_millis(), grain_count);
+    Ok(grain_count)
+}
+
+pub fn render_layer(params: &GrainParams) -> Result<(RgbaImage, usize), String> {
+    let mut data = Vec::with_capacity(params.width as usize * params.height as usize * 4);
+    let grain_count = render_layer_bands(params, |band| {
+        data.extend_from_slice(band);
+        Ok(())
+    })?;
+    let image = RgbaImage::from_raw(params.width, params.height, data).ok_or("Boolean render has the wrong size")?;
+    Ok((image, grain_count))
+}
+
+// Adds the model's grain to a photo. Each dye layer of a colour stock is exposed by its own
+// channel and gets its own grain field; black-and-white stocks are exposed by luminance and
+// add the same grain to every channel, so it stays neutral.
+pub fn render_photo(params: &GrainParams, photo: &mut RgbaImage) -> Result<(), String> {
+    let render_start = std::time::Instant::now();
+    let fields = load_fields(params)?;
+    let (width, height) = photo.dimensions();
+
+    let mut grain = vec![0i16; width as usize * height as usize];
+    let layered = fields.len() > 1;
+    for (index, (model, seed)) in fields.iter().enumerate() {
+        let channels = if layered { vec![index] } else { vec![0, 1, 2] };
+        let tones: Vec<u8> = photo
+            .as_raw()
+            .chunks_exact(4)
+            .map(|pixel| match channels[..] {
+                [channel] => pixel[channel],
+                _ => luminance([pixel[0], pixel[1], pixel[2]].map(f32::from)).round() as u8,
+            })
+            .collect();
+        let tone = |x: i32, y: i32| tones[y as usize * width as usize + x as usize];
+        model.render_plane(&tone, *seed, (width, height), 0, &mut grain);
+
+        for (pixel, grain) in photo.chunks_exact_mut(4).zip(&grain) {
+            for &channel in &channels {
+                pixel[channel] = (pixel[channel] as i16 + grain).clamp(0, 255) as u8;
+            }
+        }
+    }
+
+    println!("⏱️ Boolean model: {}x{} photo in {}ms", width, height, render_start.elapsed().as_millis());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Mean and standard deviation of the grain on a flat field of one tone
+    fn flat_field_grain(model: &BooleanModel, tone: u8) -> (f32, f32) {
+        let mut plane = vec![0i16; 96 * 96];
+        model.render_plane(&|_, _| tone, 7, (96, 96), 0, &mut plane);
+        let count = plane.len() as f32;
+        let mean = plane.iter().map(|&grain| grain as f32).sum::<f32>() / count;
+        let variance = plane.iter().map(|&grain| (grain as f32 - mean).powi(2)).sum::<f32>() / count;
+        (mean, variance.sqrt())
+    }
+
+    #[test]
+    fn flat_fields_keep_their_tone() {
+        let model = BooleanModel::from_grain(1.0, 0.3, 2.0, 1.0, &CharacteristicCurve::default());
+        for tone in [16u8, 64, 128, 224] {
+            let (mean, deviation) = flat_field_grain(&model, tone);
+            assert!(deviation > 1.0, "tone {} has no grain", tone);
+            assert!(mean.abs() < deviation * 0.1, "tone {} shifted by {} (grain {})", tone, mean, deviation);
+        }
+    }
+
+    #[test]
+    fn grain_follows_the_characteristic_curve() {
+        let curve = CharacteristicCurve::default();
+        let model = BooleanModel::from_grain(1.0, 0.3, 2.0, 1.0, &curve);
+        // The toe squeezes shadow tones together, so their grain is magnified
+        let long_toe = BooleanModel::from_grain(1.0, 0.3, 2.0, 1.0, &CharacteristicCurve { toe: 0.8, ..curve });
+        assert!(long_toe.grain_gain[64] > model.grain_gain[64]);
+        assert!(model.grain_gain[64] > model.grain_gain[128]);
+
+        // Thin densities develop coarser grain
+        assert!(model.radius_log_mean[16] > model.radius_log_mean[128]);
+        assert!(model.radius_log_mean[128] > model.radius_log_mean[240]);
+    }
+
+    #[test]
+    fn denser_emulsions_crowd_in_more_grain() {
+        let curve = CharacteristicCurve::default();
+        let model = BooleanModel::from_grain(1.0, 0.3, 2.0, 1.0, &curve);
+        let doubled = BooleanModel::from_grain(1.0, 0.3, 2.0, 2.0, &curve);
+        assert!((doubled.intensity[100] - 2.0 * model.intensity[100]).abs() < 1e-4);
+        for tone in [64u8, 160] {
+            let ((mean, deviation), (doubled_mean, doubled_deviation)) = (flat_field_grain(&model, tone), flat_field_grain(&doubled, tone));
+            assert!((deviation - doubled_deviation).abs() > deviation * 0.1, "tone {}: {} vs {}", tone, deviation, doubled_deviation);
+            assert!(mean.abs() < deviation * 0.1 && doubled_mean.abs() < doubled_deviation * 0.1);
+        }
+    }
+
+    #[test]
+    fn dye_layers_keep_their_own_density() {
+        let params: GrainParams = serde_json::from_str(concat!(
+            r#"{"film_stock":"Kodak Portra 400","exposure_compensation":0,"size_multiplier":1,"contrast":100,"#,
+            r#""grain_density":1000,"width":64,"height":64,"background":"transparent"}"#
+        ))
+        .unwrap();
+        let layers = StockData::load(&params.film_stock).unwrap().stock.dye_layers.layers();
+        let fields = load_fields(&params).unwrap();
+        assert_eq!(fields.len(), 3);
+        // Denser layers cover more of their channel at the same tone
+        for (a, b) in [(0, 1), (1, 2), (0, 2)] {
+            let (denser, thinner) = if layers[a].density_scale > layers[b].density_scale { (a, b) } else { (b, a) };
+            assert!(fields[denser].0.expected_coverage[128] > fields[thinner].0.expected_coverage[128], "layers {} and {}", a, b);
+        }
+    }
+
+    #[test]
+    fn preview_overlay_reproduces_each_channel() {
+        assert_eq!(preview_overlay([0; 3]), [255, 255, 255, 0]);
+        assert_eq!(preview_overlay([40; 3])[..3], [255; 3]);
+        assert_eq!(preview_overlay([-40; 3])[..3], [0; 3]);
+        for grain in [[30i16, -10, 5], [-60, 20, 0], [100, 100, -128]] {
+            let [r, g, b, a] = preview_overlay(grain);
+            let alpha = a as f32 / 255.0;
+            for (color, grain) in [r, g, b].into_iter().zip(grain) {
+                let composited = PREVIEW_TONE as f32 * (1.0 - alpha) + color as f32 * alpha;
+                assert!((composited - (PREVIEW_TONE as i16 + grain).clamp(0, 255) as f32).abs() < 1.5, "{:?}", grain);
+            }
+        }
+    }
+
+    #[test]
+    fn cells_keep_their_grains_as_tone_rises() {
+        let model = BooleanModel::from_grain(1.0, 0.3, 3.0, 1.0, &CharacteristicCurve::default());
+        let (mut darker, mut brighter) = (Vec::new(), Vec::new());
+        for x in 0..64 {
+            model.cell_grains(3, x, 5, 64, &mut darker);
+            model.cell_grains(3, x, 5, 192, &mut brighter);
+        }
+        assert!(brighter.len() > darker.len());
+        assert!(darker.iter().all(|grain| brighter.iter().any(|other| other.x == grain.x && other.y == grain.y)));
+    }
+}
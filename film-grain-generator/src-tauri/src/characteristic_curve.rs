@@ -0,0 +1,228 @@
+use image::{GrayImage, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::color_response::luminance;
+use crate::development::Development;
+use crate::{get_json_contrast_multiplier, Grain};
+
+// Mid-grey (18% scene luminance) sits this far, in log10 exposure, above the point where
+// the straight line meets base+fog, on every curve
+const GREY_ABOVE_FOG: f32 = 1.0;
+const MID_GREY: f32 = 0.18;
+const MIN_LUMINANCE: f32 = 1e-4; // Pure black would be an infinitely low exposure
+// Thin exposures develop only the largest, most sensitive crystals: grain at base+fog is this
+// much larger than at mid-grey, and shrinks as density rises
+const TOE_GRAIN_GROWTH: f32 = 0.5;
+const GRAIN_SIZE_RANGE: (f32, f32) = (0.8, 1.4);
+// Toe and shoulder flatten towards zero slope; cap how much they magnify grain there
+const MAX_GRAIN_GAIN: f32 = 3.0;
+
+// A stock's characteristic (Hurter-Driffield) curve: developed density against log10
+// exposure. A soft toe rises out of base+fog into the straight line of slope gamma, which
+// rolls off through the shoulder into d_max. Toe and shoulder are widths in log exposure.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CharacteristicCurve {
+    pub base_fog: f32,
+    pub gamma: f32,
+    pub toe: f32,
+    pub shoulder: f32,
+    pub d_max: f32,
+}
+
+impl Default for CharacteristicCurve {
+    fn default() -> Self {
+        Self { base_fog: 0.2, gamma: 0.62, toe: 0.4, shoulder: 0.6, d_max: 2.4 }
+    }
+}
+
+impl CharacteristicCurve {
+    // Fallback for stocks without curve data, from their categorical descriptions: contrast
+    // steepens the line, visible shadow grain means a long toe, visible highlights a long shoulder
+    pub fn from_descriptions(film_type: &str, contrast_level: &str, shadow_visibility: &str, highlight_visibility: &str) -> Self {
+        let base = Self::default();
+        let gamma = if film_type == "bw" { 0.65 } else { base.gamma };
+        let visibility_width = |visibility: &str, medium: f32| match visibility {
+            "very low" => medium * 0.5,
+            "low" => medium * 0.75,
+            "high" => medium * 1.25,
+            "very high" => medium * 1.5,
+            _ => medium,
+        };
+        Self {
+            gamma: gamma * get_json_contrast_multiplier(contrast_level),
+            toe: visibility_width(shadow_visibility, base.toe),
+            shoulder: visibility_width(highlight_visibility, base.shoulder),
+            ..base
+        }
+    }
+
+    // Fields given under "characteristic_curve" in the stock JSON, the rest from `fallback`
+    pub fn from_json(data: Option<&serde_json::Value>, fallback: Self) -> Self {
+        let field = |name: &str, default: f32| {
+            data.and_then(|curve| curve.get(name)).and_then(|v| v.as_f64()).map_or(default, |v| v as f32)
+        };
+        Self {
+            base_fog: field("base_fog", fallback.base_fog),
+            gamma: field("gamma", fallback.gamma),
+            toe: field("toe", fallback.toe),
+            shoulder: field("shoulder", fallback.shoulder),
+            d_max: field("d_max", fallback.d_max),
+        }
+    }
+
+    // Density at a log10 exposure relative to mid-grey
+    pub fn density(&self, log_exposure: f32) -> f32 {
+        let above_fog = log_exposure + GREY_ABOVE_FOG;
+        let toe = self.toe.max(1e-3);
+        let straight = self.base_fog + self.gamma * toe * softplus(above_fog / toe);
+        // Soft minimum of the straight line and d_max
+        let shoulder = (self.shoulder * self.gamma).max(1e-3);
+        self.d_max - shoulder * softplus((self.d_max - straight) / shoulder)
+    }
+
+    // Local contrast, d density / d log exposure
+    pub fn slope(&self, log_exposure: f32) -> f32 {
+        const STEP: f32 = 0.01;
+        (self.density(log_exposure + STEP) - self.density(log_exposure - STEP)) / (2.0 * STEP)
+    }
+
+    // How far a density is from base+fog towards d_max, from 0 to 1
+    pub fn density_fraction(&self, density: f32) -> f32 {
+        ((density - self.base_fog) / (self.d_max - self.base_fog).max(1e-3)).clamp(0.0, 1.0)
+    }
+
+    // The curve a development gives: steeper when pushed, over more fog
+    pub fn developed(&self, development: &Development) -> Self {
+        Self { gamma: self.gamma * development.contrast, base_fog: self.base_fog + development.fog, ..*self }
+    }
+
+    // Size of the grain developed at a density, relative to mid-grey's
+    pub fn grain_size_scale(&self, density: f32) -> f32 {
+        let grey_fraction = self.density_fraction(self.density(0.0));
+        (1.0 + TOE_GRAIN_GROWTH * (grey_fraction - self.density_fraction(density))).clamp(GRAIN_SIZE_RANGE.0, GRAIN_SIZE_RANGE.1)
+    }
+
+    // How much the curve magnifies grain at a log exposure: where the toe or shoulder squeezes
+    // tones together, the same grain is a bigger share of the tonal steps
+    pub fn grain_gain(&self, log_exposure: f32) -> f32 {
+        (self.gamma / self.slope(log_exposure).max(1e-3)).min(MAX_GRAIN_GAIN)
+    }
+}
+
+// A photo's tones placed on a curve, for stamped grain composited over it: how strongly grain
+// shows and how large it grows at each tone, relative to mid-grey
+pub struct PhotoTones {
+    tones: GrayImage,
+    visibility: [f32; 256],
+    size: [f32; 256],
+}
+
+impl PhotoTones {
+    pub fn new(curve: &CharacteristicCurve, photo: &RgbaImage) -> Self {
+        // Opaque grains covering 1 - 10^-D of the film vary most around half coverage: thin
+        // tones show only a few specks, dense ones bury grains under each other
+        let fluctuation = |density: f32| {
+            let coverage = 1.0 - 10f32.powf(-density);
+            (coverage * (1.0 - coverage)).sqrt()
+        };
+        let grey = fluctuation(curve.density(0.0)).max(1e-3) * curve.grain_gain(0.0);
+        let log_exposure: [f32; 256] = std::array::from_fn(|value| log_exposure_of_value(value as u8));
+        let density = log_exposure.map(|log_exposure| curve.density(log_exposure));
+        Self {
+            tones: GrayImage::from_fn(photo.width(), photo.height(), |x, y| {
+                let pixel = photo.get_pixel(x, y);
+                image::Luma([tone([pixel[0], pixel[1], pixel[2]])])
+            }),
+            visibility: std::array::from_fn(|value| fluctuation(density[value]) * curve.grain_gain(log_exposure[value]) / grey),
+            size: density.map(|density| curve.grain_size_scale(density)),
+        }
+    }
+
+    // Grain alpha multiplier for each 8-bit tone beneath it
+    pub fn visibility(&self) -> &[f32; 256] {
+        &self.visibility
+    }
+
+    // Grows or shrinks each grain to the size the tone under its centre develops
+    pub fn scale_grains(&self, grains: &mut [Grain]) {
+        let (width, height) = self.tones.dimensions();
+        for grain in grains {
+            let x = (grain.x.max(0.0) as u32).min(width.saturating_sub(1));
+            let y = (grain.y.max(0.0) as u32).min(height.saturating_sub(1));
+            grain.size *= self.size[self.tones.get_pixel(x, y)[0] as usize];
+        }
+    }
+}
+
+// 8-bit tone of an sRGB pixel
+pub fn tone(rgb: [u8; 3]) -> u8 {
+    luminance(rgb.map(|c| c as f32)).round().clamp(0.0, 255.0) as u8
+}
+
+// Log10 exposure relative to mid-grey for an 8-bit sRGB scene value
+pub fn log_exposure_of_value(value: u8) -> f32 {
+    let encoded = value as f32 / 255.0;
+    let luminance = if encoded <= 0.04045 { encoded / 12.92 } else { ((encoded + 0.055) / 1.055).powf(2.4) };
+    (luminance.max(MIN_LUMINANCE) / MID_GREY).log10()
+}
+
+fn softplus(x: f32) -> f32 {
+    x.max(0.0) + (-x.abs()).exp().ln_1p()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn density_rises_from_base_fog_to_d_max() {
+        for curve in [CharacteristicCurve::default(), CharacteristicCurve { toe: 0.1, shoulder: 0.3, gamma: 0.9, ..CharacteristicCurve::default() }] {
+            let densities: Vec<f32> = (0..=255u8).map(|value| curve.density(log_exposure_of_value(value))).collect();
+            assert!(densities.windows(2).all(|pair| pair[1] >= pair[0]), "{:?}", curve);
+            // The shoulder's soft minimum takes a hair off even the thinnest densities
+            assert!((densities[0] - curve.base_fog).abs() < 0.01 && densities[255] <= curve.d_max, "{:?}", curve);
+            assert!((curve.slope(0.0) - curve.gamma).abs() < curve.gamma * 0.2, "{:?}", curve);
+        }
+    }
+
+    #[test]
+    fn photo_tones_follow_the_curve() {
+        let curve = CharacteristicCurve::default();
+        let photo = RgbaImage::from_fn(256, 1, |x, _| image::Rgba([x as u8, x as u8, x as u8, 255]));
+        let tones = PhotoTones::new(&curve, &photo);
+        let grey = tone([118, 118, 118]) as usize;
+        assert!((tones.visibility()[grey] - 1.0).abs() < 0.05, "{}", tones.visibility()[grey]);
+        // The toe squeezes shadows together and magnifies their grain; the straight line's
+        // highlights show it less. Thin tones develop only the largest crystals.
+        assert!(tones.visibility()[20] > 1.5 && tones.visibility()[230] < 1.0);
+        assert!(tones.size[0] > tones.size[grey] && tones.size[grey] > tones.size[255]);
+
+        let grain = |x: f32| Grain { x, y: 0.5, size: 2.0, opacity: 1.0, shape_factor: 1.0, angle: 0.0, dye_layer: None };
+        let mut grains = [grain(5.5), grain(250.5)];
+        tones.scale_grains(&mut grains);
+        assert!(grains[0].size > 2.0 && grains[1].size < 2.0, "{:?}", grains);
+    }
+
+    #[test]
+    fn missing_curve_data_falls_back_field_by_field() {
+        let fallback = CharacteristicCurve::from_descriptions("color", "medium", "medium", "medium");
+        let empty = CharacteristicCurve::from_json(None, fallback);
+        assert_eq!((empty.gamma, empty.toe, empty.d_max), (fallback.gamma, fallback.toe, fallback.d_max));
+
+        let partial = serde_json::json!({ "gamma": 0.9, "d_max": "high" });
+        let curve = CharacteristicCurve::from_json(Some(&partial), fallback);
+        assert_eq!((curve.gamma, curve.d_max, curve.base_fog), (0.9, fallback.d_max, fallback.base_fog));
+    }
+
+    #[test]
+    fn descriptions_shape_the_fallback_curve() {
+        let medium = CharacteristicCurve::from_descriptions("color", "medium", "medium", "medium");
+        let contrasty = CharacteristicCurve::from_descriptions("color", "high", "medium", "medium");
+        assert!(contrasty.gamma > medium.gamma);
+        assert!(CharacteristicCurve::from_descriptions("bw", "medium", "medium", "medium").gamma > medium.gamma);
+
+        let visible = CharacteristicCurve::from_descriptions("color", "medium", "very high", "low");
+        assert!(visible.toe > medium.toe && visible.shoulder < medium.shoulder);
+        assert_eq!(CharacteristicCurve::from_descriptions("color", "medium", "unknown", "unknown").toe, medium.toe);
+    }
+}
@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+
+use crate::characteristic_curve::tone;
+use crate::lerp;
+
+// Rec. 709 luminance weights
+const LUMA: [f32; 3] = [0.2126, 0.7152, 0.0722];
+// Tinted grain keeps its hue in near-black areas without dividing by nothing
+const MIN_LUMINANCE: f32 = 0.02;
+// Grain hue never strays further than this multiple of its tone in any channel
+const MAX_CHROMA_RATIO: f32 = 2.5;
+
+// Where each grain's colour comes from when composited onto a photo
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GrainColorSource {
+    #[default]
+    Stock, // The stock's grain colour from color.json
+    Image, // The photo's colour under the grain, through the stock's colour response
+}
+
+// How a stock's grain shows in each colour, from the "color_response" section of
+// imagecolors.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColorResponse {
+    pub red_grain_visibility: f32,
+    pub green_grain_visibility: f32,
+    pub blue_grain_visibility: f32,
+    pub saturation_effect: f32, // How much of the image colour's saturation the grain keeps
+}
+
+impl Default for ColorResponse {
+    // Neutral: grain takes the image colour as it is, equally visible in every channel
+    fn default() -> Self {
+        Self { red_grain_visibility: 1.0, green_grain_visibility: 1.0, blue_grain_visibility: 1.0, saturation_effect: 1.0 }
+    }
+}
+
+impl ColorResponse {
+    // Grain strength in each channel relative to the average, so overall grain strength
+    // stays as rendered
+    fn channel_strengths(&self) -> [f32; 3] {
+        let visibility = [self.red_grain_visibility, self.green_grain_visibility, self.blue_grain_visibility];
+        let mean = (visibility.iter().sum::<f32>() / 3.0).max(0.01);
+        visibility.map(|v| v / mean)
+    }
+
+    pub fn blend(a: &Self, b: &Self, t: f32) -> Self {
+        Self {
+            red_grain_visibility: lerp(a.red_grain_visibility, b.red_grain_visibility, t),
+            green_grain_visibility: lerp(a.green_grain_visibility, b.green_grain_visibility, t),
+            blue_grain_visibility: lerp(a.blue_grain_visibility, b.blue_grain_visibility, t),
+            saturation_effect: lerp(a.saturation_effect, b.saturation_effect, t),
+        }
+    }
+
+    // Colour of a grain of the given tone formed under `image`: the dye layers that made it
+    // recorded the image's colour, so it carries that hue at the stock's saturation
+    pub fn grain_color(&self, tone: f32, image: [u8; 3]) -> [f32; 3] {
+        let image = image.map(|c| c as f32 / 255.0);
+        let luminance = luminance(image).max(MIN_LUMINANCE);
+        image.map(|c| {
+            let chroma_ratio = (1.0 + (c / luminance - 1.0) * self.saturation_effect).clamp(0.0, MAX_CHROMA_RATIO);
+            (tone * chroma_ratio).clamp(0.0, 255.0)
+        })
+    }
+}
+
+// Weighted by LUMA, in the scale of the channels given
+pub fn luminance(rgb: [f32; 3]) -> f32 {
+    rgb.iter().zip(LUMA).map(|(c, w)| c * w).sum()
+}
+
+// Alpha blends RGBA grain over RGBA base pixels like a plain composite, but each grain pixel
+// takes its hue from the base pixel beneath it and shows per channel as the stock's
+// colour response says. The grain's own colour only contributes its tone, and its alpha is
+// scaled by `visibility` at the base pixel's tone.
+pub fn composite_image_colored_rows(base: &mut [u8], grain: &[u8], response: &ColorResponse, visibility: &[f32; 256]) {
+    let strengths = response.channel_strengths();
+    for (base_pixel, grain_pixel) in base.chunks_exact_mut(4).zip(grain.chunks_exact(4)) {
+        if grain_pixel[3] == 0 {
+            continue;
+        }
+        let grain_alpha = grain_pixel[3] as f32 / 255.0 * visibility[tone([base_pixel[0], base_pixel[1], base_pixel[2]]) as usize];
+        let tone = luminance([grain_pixel[0] as f32, grain_pixel[1] as f32, grain_pixel[2] as f32]);
+        let color = response.grain_color(tone, [base_pixel[0], base_pixel[1], base_pixel[2]]);
+        for ((channel, grain_color), strength) in base_pixel.iter_mut().zip(color).zip(strengths) {
+            let alpha = (grain_alpha * strength).min(1.0);
+            *channel = (*channel as f32 * (1.0 - alpha) + grain_color * alpha) as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grain_takes_the_hue_beneath_it() {
+        let response = ColorResponse::default();
+        let gray = response.grain_color(100.0, [128, 128, 128]);
+        assert!(gray.iter().all(|c| (c - 100.0).abs() < 0.5), "{:?}", gray);
+
+        let red = response.grain_color(100.0, [200, 60, 60]);
+        assert!(red[0] > 100.0 && red[1] < 100.0 && red[2] < 100.0, "{:?}", red);
+        let muted = ColorResponse { saturation_effect: 0.5, ..ColorResponse::default() }.grain_color(100.0, [200, 60, 60]);
+        assert!(muted[0] < red[0] && muted[0] > 100.0);
+    }
+
+    #[test]
+    fn visibility_sets_each_channels_grain_strength() {
+        let response = ColorResponse { blue_grain_visibility: 2.0, ..ColorResponse::default() };
+        let mut base = [128, 128, 128, 255];
+        composite_image_colored_rows(&mut base, &[0, 0, 0, 64], &response, &[1.0; 256]);
+        assert!(base[2] < base[1] && base[0] == base[1], "{:?}", base);
+        assert!(crate::load_image_responses().unwrap().values().all(|stock| stock.color_response.saturation_effect > 0.0));
+    }
+}
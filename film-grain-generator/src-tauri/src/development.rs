@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{FilmStock, GrainParams};
+
+// Pulled film changes less than pushed film: pull stops count this much of a push stop
+const PULL_RESPONSE: f32 = 0.5;
+// Pushing raises chemical fog by this much density per stop
+const FOG_PER_PUSH_STOP: f32 = 0.04;
+// Pushing spreads the size distribution: the largest crystals gain the most
+const SIZE_SPREAD_PER_PUSH_STOP: f32 = 0.1;
+const PUSH_RANGE: (f32, f32) = (-3.0, 4.0);
+
+// How a stock responds to development, from the "development_response" stock data
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct DevelopmentResponse {
+    pub grain_growth_per_stop: f32, // Relative grain size increase per stop of push
+    pub contrast_per_stop: f32,     // Relative gamma increase per stop of push
+    pub clumping_per_stop: f32,     // Relative clustering increase per stop of push
+    pub edge_effect: f32,           // How strongly developer adjacency effects show, 0 to 1
+}
+
+impl Default for DevelopmentResponse {
+    fn default() -> Self {
+        Self { grain_growth_per_stop: 0.12, contrast_per_stop: 0.1, clumping_per_stop: 0.18, edge_effect: 0.8 }
+    }
+}
+
+impl DevelopmentResponse {
+    // Fallback for stocks without response data. Tabular crystals push with the least grain
+    // growth and, in their thin emulsions, show edge effects most; colour stocks' masked
+    // dye layers gain less contrast than silver
+    pub fn from_crystal_type(crystal_type: &str, film_type: &str) -> Self {
+        let base = Self::default();
+        let (grain_growth_per_stop, clumping_per_stop, edge_effect) = match crystal_type {
+            "tabular" => (0.07, 0.1, 1.0),
+            "sigma" => (0.09, 0.14, 0.9),
+            _ => (base.grain_growth_per_stop, base.clumping_per_stop, base.edge_effect),
+        };
+        let contrast_per_stop = if film_type == "bw" { 0.14 } else { 0.08 };
+        Self { grain_growth_per_stop, contrast_per_stop, clumping_per_stop, edge_effect }
+    }
+
+    // Fields given under "development_response" in the stock JSON, the rest from `fallback`
+    pub fn from_json(data: Option<&serde_json::Value>, fallback: Self) -> Self {
+        let field = |name: &str, default: f32| {
+            data.and_then(|response| response.get(name)).and_then(|v| v.as_f64()).map_or(default, |v| v as f32)
+        };
+        Self {
+            grain_growth_per_stop: field("grain_growth_per_stop", fallback.grain_growth_per_stop),
+            contrast_per_stop: field("contrast_per_stop", fallback.contrast_per_stop),
+            clumping_per_stop: field("clumping_per_stop", fallback.clumping_per_stop),
+            edge_effect: field("edge_effect", fallback.edge_effect),
+        }
+    }
+}
+
+// What one development (push/pull, developer and agitation) does to a stock's grain.
+// Every field is a multiplier, 1.0 for box-speed development in a standard developer,
+// except fog, which is added density.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Development {
+    pub grain_size: f32,
+    pub size_spread: f32,
+    pub clumping: f32,
+    pub contrast: f32,
+    pub edge_softness: f32,
+    pub fog: f32,
+}
+
+// (grain size, clumping, contrast, edge softness) for each developer type
+fn developer_factors(developer: &str) -> (f32, f32, f32, f32) {
+    match developer {
+        "fine-grain" => (0.9, 0.85, 0.95, 1.15),    // Perceptol, Microdol-X
+        "solvent" => (0.85, 0.8, 0.92, 1.35),       // Silver solvent etches crystal edges: finer, mushier
+        "high-acutance" => (1.1, 1.15, 1.05, 0.6),  // Rodinal, FX-1: crisp grain with edge effects
+        _ => (1.0, 1.0, 1.0, 1.0),                  // Standard (D-76, ID-11)
+    }
+}
+
+// (clumping, contrast, edge softness) for each agitation scheme
+fn agitation_factors(agitation: &str) -> (f32, f32, f32) {
+    match agitation {
+        "stand" => (1.3, 0.9, 0.7), // Exhausted developer at edges: strongest adjacency effects, bromide drag
+        "gentle" => (1.1, 0.95, 0.85),
+        "vigorous" => (0.9, 1.08, 1.15), // Fresh developer everywhere: even, contrastier, fewer edge effects
+        _ => (1.0, 1.0, 1.0),
+    }
+}
+
+impl Development {
+    pub fn for_params(params: &GrainParams, stock: &FilmStock) -> Self {
+        Self::new(
+            &stock.development_response,
+            params.push_pull_stops.unwrap_or(0.0),
+            params.developer.as_deref().unwrap_or("standard"),
+            params.agitation.as_deref().unwrap_or("normal"),
+        )
+    }
+
+    fn new(response: &DevelopmentResponse, stops: f32, developer: &str, agitation: &str) -> Self {
+        let stops = stops.clamp(PUSH_RANGE.0, PUSH_RANGE.1);
+        let effective_stops = if stops < 0.0 { stops * PULL_RESPONSE } else { stops };
+        let (developer_size, developer_clumping, developer_contrast, developer_edge) = developer_factors(developer);
+        let (agitation_clumping, agitation_contrast, agitation_edge) = agitation_factors(agitation);
+
+        // Edge effects show as far as the emulsion lets them
+        let edge_softness = 1.0 + (developer_edge * agitation_edge - 1.0) * response.edge_effect;
+        Self {
+            grain_size: (1.0 + response.grain_growth_per_stop * effective_stops).max(0.5) * developer_size,
+            size_spread: 1.0 + SIZE_SPREAD_PER_PUSH_STOP * stops.max(0.0),
+            clumping: (1.0 + response.clumping_per_stop * effective_stops).max(0.5) * developer_clumping * agitation_clumping,
+            contrast: (1.0 + response.contrast_per_stop * effective_stops).max(0.5) * developer_contrast * agitation_contrast,
+            edge_softness: edge_softness.max(0.2),
+            fog: FOG_PER_PUSH_STOP * stops.max(0.0),
+        }
+    }
+
+    pub fn is_normal(&self) -> bool {
+        *self == Self { grain_size: 1.0, size_spread: 1.0, clumping: 1.0, contrast: 1.0, edge_softness: 1.0, fog: 0.0 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn box_speed_in_standard_developer_changes_nothing() {
+        let response = DevelopmentResponse::default();
+        assert!(Development::new(&response, 0.0, "standard", "normal").is_normal());
+        assert!(Development::new(&response, 0.0, "unknown", "").is_normal());
+    }
+
+    #[test]
+    fn pushing_coarsens_and_pulling_refines_less() {
+        let response = DevelopmentResponse::default();
+        let normal = Development::new(&response, 0.0, "standard", "normal");
+        let pushed = Development::new(&response, 2.0, "standard", "normal");
+        let pulled = Development::new(&response, -2.0, "standard", "normal");
+        assert!(pushed.grain_size > normal.grain_size && pushed.contrast > normal.contrast && pushed.clumping > normal.clumping);
+        assert!(pushed.fog > 0.0 && pulled.fog == 0.0);
+        assert!(pulled.grain_size < normal.grain_size);
+        assert!(pushed.grain_size - 1.0 > 1.0 - pulled.grain_size);
+    }
+
+    #[test]
+    fn edge_effects_follow_the_emulsion() {
+        let thin = DevelopmentResponse::from_crystal_type("tabular", "bw");
+        let thick = DevelopmentResponse::from_crystal_type("cubic", "bw");
+        let rodinal_stand = |response| Development::new(response, 0.0, "high-acutance", "stand").edge_softness;
+        assert!(rodinal_stand(&thin) < rodinal_stand(&thick));
+        assert!(rodinal_stand(&thick) < 1.0);
+        assert!(Development::new(&thick, 0.0, "solvent", "normal").edge_softness > 1.0);
+    }
+}
@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{lerp, motion_effects, split_mix64, GrainParams};
+
+// How much of its complementary primary a dye cloud holds back in the grain colour
+const DYE_ABSORPTION: f32 = 0.55;
+// Keeps layer seeds apart from the stock seed and from tile seeds
+const LAYER_SEED_SALT: u64 = 0xd7e1_a7e5;
+
+// One emulsion layer of a colour stock, relative to the stock's size metrics
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct DyeLayer {
+    pub size_scale: f32,    // Grain size relative to the stock average
+    pub density_scale: f32, // Share of the stock's grains relative to an even third
+}
+
+// Colour film's three emulsion layers, each forming its own dye clouds: cyan in the
+// red-sensitive layer, magenta in the green, yellow in the blue
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct DyeLayers {
+    pub cyan: DyeLayer,
+    pub magenta: DyeLayer,
+    pub yellow: DyeLayer,
+}
+
+impl Default for DyeLayers {
+    // Typical colour negative: the fast blue-sensitive layer is the coarsest and sparsest,
+    // the green layer the finest since it carries most of the image's detail
+    fn default() -> Self {
+        Self {
+            cyan: DyeLayer { size_scale: 1.0, density_scale: 1.0 },
+            magenta: DyeLayer { size_scale: 0.9, density_scale: 1.15 },
+            yellow: DyeLayer { size_scale: 1.2, density_scale: 0.85 },
+        }
+    }
+}
+
+impl DyeLayers {
+    // Layers given under "dye_layers" in the stock JSON, the rest from the defaults
+    pub fn from_json(data: Option<&serde_json::Value>) -> Self {
+        let defaults = Self::default();
+        let layer = |name: &str, default: DyeLayer| {
+            let field = |field: &str, fallback: f32| {
+                data.and_then(|layers| layers.get(name)).and_then(|layer| layer.get(field)).and_then(|v| v.as_f64()).map_or(fallback, |v| v as f32)
+            };
+            DyeLayer { size_scale: field("size_scale", default.size_scale), density_scale: field("density_scale", default.density_scale) }
+        };
+        Self {
+            cyan: layer("cyan", defaults.cyan),
+            magenta: layer("magenta", defaults.magenta),
+            yellow: layer("yellow", defaults.yellow),
+        }
+    }
+
+    pub fn blend(a: &Self, b: &Self, t: f32) -> Self {
+        let blend_layer = |a: DyeLayer, b: DyeLayer| DyeLayer {
+            size_scale: lerp(a.size_scale, b.size_scale, t),
+            density_scale: lerp(a.density_scale, b.density_scale, t),
+        };
+        Self {
+            cyan: blend_layer(a.cyan, b.cyan),
+            magenta: blend_layer(a.magenta, b.magenta),
+            yellow: blend_layer(a.yellow, b.yellow),
+        }
+    }
+
+    // In the order of the RGB channels they absorb
+    pub fn layers(&self) -> [DyeLayer; 3] {
+        [self.cyan, self.magenta, self.yellow]
+    }
+}
+
+// Params for generating one layer's grain field on its own: its own seed, grain size and a
+// share of the stock's grains, so the three layers together keep the stock's total
+pub fn layer_params(params: &GrainParams, layer: &DyeLayer, index: usize) -> GrainParams {
+    let mut layer_params = params.clone();
+    // The frame is folded into the layer's seed
+    layer_params.seed = Some(layer_seed(motion_effects::grain_seed(params), index));
+    layer_params.frame_index = None;
+    layer_params.size_multiplier *= layer.size_scale;
+    layer_params.grain_density = ((params.grain_density as f32 * layer.density_scale / 3.0).round() as u32).max(1);
+    layer_params
+}
+
+pub fn layer_seed(seed: u64, index: usize) -> u64 {
+    split_mix64(seed ^ LAYER_SEED_SALT ^ index as u64)
+}
+
+// RGB multipliers for a grain in the layer at `index`: its dye holds back the channel
+// that layer records, so cyan grains lack red, magenta green and yellow blue
+pub fn layer_tint(index: u8) -> [f32; 3] {
+    let mut tint = [1.0; 3];
+    if let Some(channel) = tint.get_mut(index as usize) {
+        *channel = 1.0 - DYE_ABSORPTION;
+    }
+    tint
+}
@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{lerp, GrainParams};
+
+// The stock curves cover this many stops either side of box speed
+const STOP_RANGE: f32 = 3.0;
+// Past the reciprocity threshold, each tenfold longer exposure loses this many stops
+const RECIPROCITY_STOPS_PER_DECADE: f32 = 1.0;
+// How a change in grain splits between coarser and more visible grains: size takes this
+// share of it (as a power), opacity the rest
+const SIZE_SHARE: f32 = 1.0 / 3.0;
+
+// A stock's grain across exposure, from the "exposure_response" section of imagecolors.json.
+// Values are grain strengths; the centre of the latitude is box speed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExposureResponse {
+    pub underexposure_grain_curve: [f32; 3],  // At -3, -2 and -1 stops
+    pub overexposure_grain_curve: [f32; 3],   // At +1, +2 and +3 stops
+    pub reciprocity_failure_threshold: f32,   // Longest exposure in seconds before the film loses speed
+    pub latitude_grain_distribution: [f32; 3], // At -1, 0 and +1 stops
+}
+
+impl Default for ExposureResponse {
+    // A typical ISO 400 colour negative, for stocks missing from imagecolors.json
+    fn default() -> Self {
+        Self {
+            underexposure_grain_curve: [2.0, 1.6, 1.2],
+            overexposure_grain_curve: [0.8, 0.6, 0.5],
+            reciprocity_failure_threshold: 1.7,
+            latitude_grain_distribution: [1.3, 1.0, 0.7],
+        }
+    }
+}
+
+impl ExposureResponse {
+    // Grain strength at -3..+3 stops. Both the under/over curves and the latitude give a
+    // value at ±1 stop; they agree closely, so those points take their mean.
+    fn curve(&self) -> [f32; 7] {
+        let (under, over, latitude) = (&self.underexposure_grain_curve, &self.overexposure_grain_curve, &self.latitude_grain_distribution);
+        [under[0], under[1], (under[2] + latitude[0]) * 0.5, latitude[1], (over[0] + latitude[2]) * 0.5, over[1], over[2]]
+    }
+
+    // Grain relative to box speed at an exposure in stops, interpolated along the curve
+    pub fn grain_factor(&self, stops: f32) -> f32 {
+        let curve = self.curve();
+        let position = stops.clamp(-STOP_RANGE, STOP_RANGE) + STOP_RANGE;
+        let index = (position as usize).min(curve.len() - 2);
+        lerp(curve[index], curve[index + 1], position - index as f32) / curve[3].max(0.01)
+    }
+
+    pub fn blend(a: &Self, b: &Self, t: f32) -> Self {
+        let blend_curve = |a: &[f32; 3], b: &[f32; 3]| std::array::from_fn(|i| lerp(a[i], b[i], t));
+        Self {
+            underexposure_grain_curve: blend_curve(&a.underexposure_grain_curve, &b.underexposure_grain_curve),
+            overexposure_grain_curve: blend_curve(&a.overexposure_grain_curve, &b.overexposure_grain_curve),
+            reciprocity_failure_threshold: lerp(a.reciprocity_failure_threshold, b.reciprocity_failure_threshold, t),
+            latitude_grain_distribution: blend_curve(&a.latitude_grain_distribution, &b.latitude_grain_distribution),
+        }
+    }
+
+    // Stops lost to reciprocity failure by an exposure this long (Schwarzschild's law)
+    pub fn reciprocity_loss(&self, shutter_time: f32) -> f32 {
+        let threshold = self.reciprocity_failure_threshold.max(0.01);
+        if shutter_time > threshold {
+            RECIPROCITY_STOPS_PER_DECADE * (shutter_time / threshold).log10()
+        } else {
+            0.0
+        }
+    }
+}
+
+// What the render's exposure does to the grain
+#[derive(Debug, Clone, Copy)]
+pub struct Exposure {
+    pub stops: f32,           // Effective exposure relative to box speed
+    pub reciprocity_loss: f32, // Stops of that lost to a long shutter time
+    pub grain_size: f32,
+    pub grain_visibility: f32,
+}
+
+impl Exposure {
+    pub fn for_params(params: &GrainParams, response: &ExposureResponse) -> Self {
+        let reciprocity_loss = params.shutter_time.map_or(0.0, |seconds| response.reciprocity_loss(seconds));
+        let stops = params.exposure_compensation - reciprocity_loss;
+        let grain = response.grain_factor(stops);
+        Self {
+            stops,
+            reciprocity_loss,
+            grain_size: grain.powf(SIZE_SHARE),
+            grain_visibility: grain.powf(1.0 - SIZE_SHARE),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_stock_has_a_falling_grain_curve() {
+        let responses = crate::load_image_responses().unwrap();
+        assert!(!responses.is_empty());
+        for (name, response) in responses.iter().map(|(name, stock)| (name, &stock.exposure_response)) {
+            assert!((response.grain_factor(0.0) - 1.0).abs() < 1e-6, "{}", name);
+            let steps: Vec<f32> = (-6..=6).map(|half_stops| response.grain_factor(half_stops as f32 * 0.5)).collect();
+            assert!(steps.windows(2).all(|pair| pair[1] < pair[0]), "{}: {:?}", name, steps);
+        }
+    }
+
+    #[test]
+    fn long_exposures_lose_speed_past_the_threshold() {
+        let response = ExposureResponse { reciprocity_failure_threshold: 2.0, ..ExposureResponse::default() };
+        assert_eq!(response.reciprocity_loss(1.0), 0.0);
+        assert!((response.reciprocity_loss(20.0) - RECIPROCITY_STOPS_PER_DECADE).abs() < 1e-5);
+    }
+}
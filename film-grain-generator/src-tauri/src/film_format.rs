@@ -0,0 +1,180 @@
+use serde::Serialize;
+
+use crate::{FilmStock, GrainParams};
+
+// Camera gate sizes, so grain can be laid out in micrometres of real film instead of pixels
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct FilmFormat {
+    pub name: &'static str,
+    pub gate_width_mm: f32,
+    pub gate_height_mm: f32,
+    pub gate_weave_um: f32, // Typical frame-to-frame drift of film running through the gate
+}
+
+pub const FILM_FORMATS: &[FilmFormat] = &[
+    FilmFormat { name: "35mm", gate_width_mm: 36.0, gate_height_mm: 24.0, gate_weave_um: 8.0 },
+    FilmFormat { name: "645", gate_width_mm: 56.0, gate_height_mm: 41.5, gate_weave_um: 10.0 },
+    FilmFormat { name: "6x7", gate_width_mm: 69.5, gate_height_mm: 56.0, gate_weave_um: 12.0 },
+    FilmFormat { name: "4x5", gate_width_mm: 121.0, gate_height_mm: 97.0, gate_weave_um: 15.0 },
+    FilmFormat { name: "Super 35", gate_width_mm: 24.89, gate_height_mm: 18.66, gate_weave_um: 6.0 }, // Pin-registered cameras
+    FilmFormat { name: "16mm", gate_width_mm: 10.26, gate_height_mm: 7.49, gate_weave_um: 10.0 },
+];
+
+const MICRONS_PER_INCH: f32 = 25_400.0;
+
+// A rendered grain is a visible clump of crystals CLUMP_SCALE times the crystal diameter,
+// so CLUMP_SCALE² crystals fill it and the covered area of film stays the same.
+// At 16 µm/px (35mm scanned at ~1600 dpi) this reproduces the old pixel-based look.
+const CLUMP_SCALE: f32 = 16.0;
+// Only about one clump in ten develops dense enough to show - the old model's divisor
+const VISIBLE_GRAIN_FRACTION: f32 = 0.1;
+// Below this average radius grains vanish between pixels; merge clumps instead so
+// small scans of large formats stay renderable and keep the same coverage
+const MIN_AVERAGE_RADIUS_PX: f32 = 0.3; // The old model's minimum grain radius
+
+impl FilmFormat {
+    // Largest centred region of a width×height image with this frame's aspect ratio,
+    // turned to match the image's orientation. Returns (x, y, width, height).
+    pub fn crop_to_aspect(&self, width: u32, height: u32) -> (u32, u32, u32, u32) {
+        let long = self.gate_width_mm.max(self.gate_height_mm);
+        let short = self.gate_width_mm.min(self.gate_height_mm);
+        let aspect = if width >= height { long / short } else { short / long };
+
+        let (crop_width, crop_height) = if width as f32 / height as f32 > aspect {
+            (((height as f32 * aspect).round() as u32).clamp(1, width), height)
+        } else {
+            (width, ((width as f32 / aspect).round() as u32).clamp(1, height))
+        };
+        ((width - crop_width) / 2, (height - crop_height) / 2, crop_width, crop_height)
+    }
+}
+
+pub fn find_film_format(name: &str) -> Option<&'static FilmFormat> {
+    FILM_FORMATS.iter().find(|format| format.name.eq_ignore_ascii_case(name))
+}
+
+// Film covered by one pixel, or None when grain is sized in plain canvas pixels
+pub fn microns_per_pixel(params: &GrainParams) -> Result<Option<f32>, String> {
+    let format = match params.film_format.as_deref() {
+        Some(name) if !name.is_empty() => {
+            Some(find_film_format(name).ok_or_else(|| format!("Unknown film format '{}'", name))?)
+        }
+        _ => None,
+    };
+
+    Ok(match (params.scan_dpi, format) {
+        (Some(dpi), _) if dpi > 0.0 => Some(MICRONS_PER_INCH / dpi),
+        // No scan resolution: the whole frame fills the canvas
+        (_, Some(format)) => {
+            let gate_long_um = format.gate_width_mm.max(format.gate_height_mm) * 1000.0;
+            Some(gate_long_um / params.width.max(params.height) as f32)
+        }
+        _ => None,
+    })
+}
+
+pub fn dpi_for_microns_per_pixel(microns_per_pixel: f32) -> f32 {
+    MICRONS_PER_INCH / microns_per_pixel
+}
+
+// How the stock's micrometre grain model maps onto this canvas
+#[derive(Debug, Clone, Copy)]
+pub struct GrainScale {
+    pub pixels_per_micron: f32, // Multiplies the grain radius computed in µm
+    pub grain_count: usize,
+    pub microns_per_pixel: Option<f32>, // None for the legacy pixel-based scale
+}
+
+impl GrainScale {
+    pub fn for_params(params: &GrainParams, stock: &FilmStock, density_multiplier: f32) -> Result<Self, String> {
+        let microns_per_pixel = match microns_per_pixel(params)? {
+            Some(microns_per_pixel) => microns_per_pixel,
+            None => return Ok(Self::legacy(params, stock, density_multiplier)),
+        };
+
+        let canvas_area_mm2 = (params.width as f32 * microns_per_pixel / 1000.0) * (params.height as f32 * microns_per_pixel / 1000.0);
+        let clumps_per_mm2 = stock.size_metrics.density_per_mm2 as f32 * VISIBLE_GRAIN_FRACTION / (CLUMP_SCALE * CLUMP_SCALE);
+
+        let mut pixels_per_micron = CLUMP_SCALE / microns_per_pixel;
+        let average_radius_px = stock.size_metrics.avg_size_um * 0.5 * pixels_per_micron;
+        let merged = (MIN_AVERAGE_RADIUS_PX / average_radius_px.max(f32::EPSILON)).powi(2).max(1.0);
+        pixels_per_micron *= merged.sqrt();
+
+        Ok(Self {
+            pixels_per_micron,
+            grain_count: (clumps_per_mm2 * canvas_area_mm2 * density_multiplier / merged) as usize,
+            microns_per_pixel: Some(microns_per_pixel),
+        })
+    }
+
+    // Sizes are treated as pixels and density is relative to a 1024×1024 canvas
+    fn legacy(params: &GrainParams, stock: &FilmStock, density_multiplier: f32) -> Self {
+        let canvas_area_ratio = (params.width as f32 * params.height as f32) / (1024.0 * 1024.0);
+        let stock_base_density = stock.size_metrics.density_per_mm2 as f32;
+        Self {
+            pixels_per_micron: 1.0,
+            grain_count: ((stock_base_density * canvas_area_ratio * density_multiplier) / 10.0) as usize,
+            microns_per_pixel: None,
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn get_film_formats() -> Result<Vec<FilmFormat>, String> {
+    Ok(FILM_FORMATS.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(width: u32, height: u32, scan_dpi: Option<f32>) -> GrainParams {
+        let mut params: GrainParams = serde_json::from_str(concat!(
+            r#"{"film_stock":"Kodak Tri-X 400","exposure_compensation":0,"size_multiplier":1,"contrast":100,"#,
+            r#""grain_density":1000,"width":0,"height":0,"background":"transparent","seed":3,"film_format":"35mm"}"#
+        ))
+        .unwrap();
+        (params.width, params.height, params.scan_dpi) = (width, height, scan_dpi);
+        params
+    }
+
+    #[test]
+    fn crops_keep_the_frame_shape_in_either_orientation() {
+        let format = find_film_format("35mm").unwrap();
+        assert_eq!(format.crop_to_aspect(4000, 2000), (500, 0, 3000, 2000));
+        assert_eq!(format.crop_to_aspect(2000, 4000), (0, 500, 2000, 3000));
+        assert_eq!(format.crop_to_aspect(3000, 3000), (0, 500, 3000, 2000));
+        assert_eq!(format.crop_to_aspect(3600, 2400), (0, 0, 3600, 2400));
+    }
+
+    #[test]
+    fn grain_looks_the_same_at_any_output_size() {
+        let stock = &crate::load_film_stock_data().unwrap()["Kodak Tri-X 400"];
+        // (grains per mm², rendered grain size in µm)
+        let on_film = |params: &GrainParams| {
+            let scale = GrainScale::for_params(params, stock, 1.0).unwrap();
+            let microns_per_pixel = scale.microns_per_pixel.unwrap();
+            let area_mm2 = params.width as f32 * params.height as f32 * (microns_per_pixel / 1000.0).powi(2);
+            (scale.grain_count as f32 / area_mm2, scale.pixels_per_micron * microns_per_pixel)
+        };
+        let close = |(a, b): ((f32, f32), (f32, f32))| ((a.0 / b.0 - 1.0).abs() < 0.01 && (a.1 / b.1 - 1.0).abs() < 0.01, a, b);
+
+        // The whole frame at two widths, and the same film scanned at two resolutions
+        for (small, large) in [(params(2400, 1600, None), params(4800, 3200, None)), (params(300, 200, Some(2000.0)), params(600, 400, Some(4000.0)))] {
+            let result = close((on_film(&small), on_film(&large)));
+            assert!(result.0, "{:?} vs {:?}", result.1, result.2);
+        }
+
+        // Generated grains agree: same count over the same film, same size on it
+        let (small, large) = (params(300, 200, Some(2000.0)), params(600, 400, Some(4000.0)));
+        let data = crate::StockData::load(&small.film_stock).unwrap();
+        let measured = |params: &GrainParams| {
+            let grains = crate::generate_stock_grains(&data, params).unwrap();
+            let microns_per_pixel = microns_per_pixel(params).unwrap().unwrap();
+            let mean_size = grains.iter().map(|grain| grain.size).sum::<f32>() / grains.len() as f32;
+            (grains.len() as f32, mean_size * microns_per_pixel)
+        };
+        let (a, b) = (measured(&small), measured(&large));
+        assert!((a.0 / b.0 - 1.0).abs() < 0.05 && (a.1 / b.1 - 1.0).abs() < 0.05, "{:?} vs {:?}", a, b);
+    }
+}
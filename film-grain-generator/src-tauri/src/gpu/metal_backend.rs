@@ -1,6 +1,6 @@
 #[cfg(target_os = "macos")]
 use super::GpuGrainRenderer;
-use crate::{FilmStock, GrainParams, Grain};
+use crate::{dye_layers, get_coupler_spread, get_crystal_shape, FilmStock, GrainParams, Grain};
 use metal::*;
 use objc::rc::autoreleasepool;
 use std::mem;
@@ -12,7 +12,9 @@ struct MetalGrain {
     size: f32,
     opacity: f32,
     shape_factor: f32,
-    _padding: [f32; 3],
+    angle: f32,
+    dye_layer: f32, // Index into layer_tints, or -1 for silver grain
+    _padding: f32,
 }
 
 #[repr(C)]
@@ -21,8 +23,11 @@ struct MetalParams {
     width: u32,
     height: u32,
     grain_count: u32,
-    _padding: u32,
+    crystal: u32, // CrystalShape discriminant
     grain_color: [f32; 4],
+    layer_tints: [[f32; 4]; 3], // Colour stocks: RGB multipliers of the cyan, magenta and yellow layers
+    coupler_spread: f32, // Dye cloud width relative to the grain for chromogenic stocks, 0 for silver
+    _padding: [f32; 3],
 }
 
 pub struct MetalGrainRenderer {
@@ -73,7 +78,9 @@ impl MetalGrainRenderer {
             size: grain.size,
             opacity: grain.opacity,
             shape_factor: grain.shape_factor,
-            _padding: [0.0; 3],
+            angle: grain.angle,
+            dye_layer: grain.dye_layer.map_or(-1.0, f32::from),
+            _padding: 0.0,
         }).collect()
     }
 
@@ -107,8 +114,14 @@ impl GpuGrainRenderer for MetalGrainRenderer {
                 width: params.width,
                 height: params.height,
                 grain_count: grains.len() as u32,
-                _padding: 0,
+                crystal: get_crystal_shape(&stock.grain_structure) as u32,
                 grain_color: self.get_grain_color(stock),
+                layer_tints: std::array::from_fn(|layer| {
+                    let [r, g, b] = dye_layers::layer_tint(layer as u8);
+                    [r, g, b, 1.0]
+                }),
+                coupler_spread: get_coupler_spread(&stock.basic_info.process).unwrap_or(0.0),
+                _padding: [0.0; 3],
             };
 
             let params_buffer = self.device.new_buffer_with_data(
@@ -6,50 +6,105 @@ struct MetalGrain {
     float size;
     float opacity;
     float shape_factor;
-    float3 padding;
+    float angle;
+    float dye_layer; // Index into layer_tints, or -1 for silver grain
+    float padding;
 };
 
 struct MetalParams {
     uint width;
     uint height;
     uint grain_count;
-    uint padding;
+    uint crystal; // 0 rounded, 1 irregular, 2 cubic, 3 tabular
     float4 grain_color;
+    float4 layer_tints[3]; // Colour stocks: cyan, magenta and yellow layers
+    float coupler_spread; // Chromogenic stocks: dye cloud width relative to the grain; 0 for silver
 };
 
 float random(float2 seed) {
     return fract(sin(dot(seed, float2(12.9898, 78.233))) * 43758.5453);
 }
 
+// Signed distance from the stock's crystal outline (negative inside), matching the CPU
+// rasteriser. Outlines have the same area as a circle of the given radius.
+float crystal_distance(float2 p, float radius, float2 seed, uint crystal) {
+    switch (crystal) {
+        case 1: {
+            // Irregular: radius bumped by two harmonics with per-grain phases
+            float theta = atan2(p.y, p.x);
+            float phase2 = random(seed) * 6.2831853;
+            float phase3 = random(seed + float2(17.0, 31.0)) * 6.2831853;
+            float bump = 0.108 * cos(2.0 * theta - phase2) + 0.072 * cos(3.0 * theta - phase3);
+            return length(p) - radius * (1.0 + bump);
+        }
+        case 2: {
+            // Cubic: rounded square
+            float corner = radius * 0.15;
+            float2 q = abs(p) - float2(radius * 0.886 - corner);
+            return length(max(q, float2(0.0))) + min(max(q.x, q.y), 0.0) - corner;
+        }
+        case 3: {
+            // Tabular: flat hexagon
+            const float3 k = float3(-0.8660254, 0.5, 0.5773503);
+            float inradius = radius * 0.952;
+            float2 q = abs(p);
+            q -= 2.0 * min(dot(k.xy, q), 0.0) * k.xy;
+            q -= float2(clamp(q.x, -k.z * inradius, k.z * inradius), inradius);
+            return length(q) * sign(q.y);
+        }
+        default:
+            return length(p) - radius;
+    }
+}
+
 float4 render_grain_at_pixel(float2 pixel_pos, MetalGrain grain, MetalParams params) {
     float2 grain_center = grain.position;
     float2 distance_vec = pixel_pos - grain_center;
     
-    // Apply shape factor for realistic grain shapes
+    // Rotate into the grain's own axes, then apply shape factor for realistic grain shapes
+    float c = cos(grain.angle);
+    float s = sin(grain.angle);
     float2 adjusted_distance = float2(
-        distance_vec.x / grain.shape_factor,
-        distance_vec.y
+        (distance_vec.x * c + distance_vec.y * s) / grain.shape_factor,
+        distance_vec.y * c - distance_vec.x * s
     );
     
-    float distance = length(adjusted_distance);
-    
-    if (distance > grain.size) {
-        return float4(0.0, 0.0, 0.0, 0.0);
-    }
-    
-    // Calculate edge falloff for realistic grain edges
     float edge_alpha = 1.0;
-    if (distance > grain.size * 0.7) {
-        edge_alpha = (grain.size - distance) / (grain.size * 0.3);
-        edge_alpha = max(edge_alpha, 0.0);
+    if (params.coupler_spread > 0.0) {
+        // Dye cloud: a Gaussian holding the same dye as the grain, cut off at 2.8 sigma
+        float sigma = max(grain.size * params.coupler_spread * 0.7071068, 1.0);
+        float deviations = length(adjusted_distance) / sigma;
+        if (deviations > 2.8) {
+            return float4(0.0, 0.0, 0.0, 0.0);
+        }
+        edge_alpha = grain.size * grain.size / (2.0 * sigma * sigma) * exp(-0.5 * deviations * deviations);
+    } else {
+        // Measured from the crystal's outline, so `distance > size` is outside it
+        float distance = grain.size + crystal_distance(adjusted_distance, grain.size, grain_center, params.crystal);
+        
+        if (distance > grain.size) {
+            return float4(0.0, 0.0, 0.0, 0.0);
+        }
+        
+        // Calculate edge falloff for realistic grain edges
+        if (distance > grain.size * 0.7) {
+            edge_alpha = (grain.size - distance) / (grain.size * 0.3);
+            edge_alpha = max(edge_alpha, 0.0);
+        }
     }
     
     // Apply opacity with some randomness for natural variation
     float noise = random(grain_center + pixel_pos * 0.1) * 0.1 - 0.05;
     float final_opacity = grain.opacity * edge_alpha * (1.0 + noise);
     
+    // Dye clouds hold back the channel their layer records
+    float3 color = params.grain_color.rgb;
+    if (grain.dye_layer >= 0.0) {
+        color *= params.layer_tints[uint(grain.dye_layer)].rgb;
+    }
+    
     return float4(
-        params.grain_color.rgb,
+        color,
         clamp(final_opacity, 0.0, 1.0)
     );
 }
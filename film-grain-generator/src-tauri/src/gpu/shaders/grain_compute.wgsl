@@ -3,15 +3,19 @@ struct GpuGrain {
     size: f32,
     opacity: f32,
     shape_factor: f32,
-    padding: vec3<f32>,
+    angle: f32,
+    dye_layer: f32, // Index into layer_tints, or -1 for silver grain
+    padding: f32,
 }
 
 struct GpuParams {
     width: u32,
     height: u32,
     grain_count: u32,
-    padding: u32,
+    crystal: u32, // 0 rounded, 1 irregular, 2 cubic, 3 tabular
     grain_color: vec4<f32>,
+    layer_tints: array<vec4<f32>, 3>, // Colour stocks: cyan, magenta and yellow layers
+    coupler_spread: f32, // Chromogenic stocks: dye cloud width relative to the grain; 0 for silver
 }
 
 @group(0) @binding(0) var<storage, read> grains: array<GpuGrain>;
@@ -22,35 +26,87 @@ fn random(seed: vec2<f32>) -> f32 {
     return fract(sin(dot(seed, vec2<f32>(12.9898, 78.233))) * 43758.5453);
 }
 
+// Signed distance from the stock's crystal outline (negative inside), matching the CPU
+// rasteriser. Outlines have the same area as a circle of the given radius.
+fn crystal_distance(p: vec2<f32>, radius: f32, seed: vec2<f32>) -> f32 {
+    switch params.crystal {
+        case 1u: {
+            // Irregular: radius bumped by two harmonics with per-grain phases
+            let theta = atan2(p.y, p.x);
+            let phase2 = random(seed) * 6.2831853;
+            let phase3 = random(seed + vec2<f32>(17.0, 31.0)) * 6.2831853;
+            let bump = 0.108 * cos(2.0 * theta - phase2) + 0.072 * cos(3.0 * theta - phase3);
+            return length(p) - radius * (1.0 + bump);
+        }
+        case 2u: {
+            // Cubic: rounded square
+            let corner = radius * 0.15;
+            let q = abs(p) - vec2<f32>(radius * 0.886 - corner);
+            return length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - corner;
+        }
+        case 3u: {
+            // Tabular: flat hexagon
+            let k = vec3<f32>(-0.8660254, 0.5, 0.5773503);
+            let inradius = radius * 0.952;
+            var q = abs(p);
+            q = q - 2.0 * min(dot(k.xy, q), 0.0) * k.xy;
+            q = q - vec2<f32>(clamp(q.x, -k.z * inradius, k.z * inradius), inradius);
+            return length(q) * sign(q.y);
+        }
+        default: {
+            return length(p) - radius;
+        }
+    }
+}
+
 fn render_grain_at_pixel(pixel_pos: vec2<f32>, grain: GpuGrain) -> vec4<f32> {
     let grain_center = grain.position;
     let distance_vec = pixel_pos - grain_center;
     
-    // Apply shape factor for realistic grain shapes
+    // Rotate into the grain's own axes, then apply shape factor for realistic grain shapes
+    let c = cos(grain.angle);
+    let s = sin(grain.angle);
     let adjusted_distance = vec2<f32>(
-        distance_vec.x / grain.shape_factor,
-        distance_vec.y
+        (distance_vec.x * c + distance_vec.y * s) / grain.shape_factor,
+        distance_vec.y * c - distance_vec.x * s
     );
     
-    let distance = length(adjusted_distance);
-    
-    if distance > grain.size {
-        return vec4<f32>(0.0, 0.0, 0.0, 0.0);
-    }
-    
-    // Calculate edge falloff for realistic grain edges
     var edge_alpha = 1.0;
-    if distance > grain.size * 0.7 {
-        edge_alpha = (grain.size - distance) / (grain.size * 0.3);
-        edge_alpha = max(edge_alpha, 0.0);
+    if params.coupler_spread > 0.0 {
+        // Dye cloud: a Gaussian holding the same dye as the grain, cut off at 2.8 sigma
+        let sigma = max(grain.size * params.coupler_spread * 0.7071068, 1.0);
+        let deviations = length(adjusted_distance) / sigma;
+        if deviations > 2.8 {
+            return vec4<f32>(0.0, 0.0, 0.0, 0.0);
+        }
+        edge_alpha = grain.size * grain.size / (2.0 * sigma * sigma) * exp(-0.5 * deviations * deviations);
+    } else {
+        // Measured from the crystal's outline, so `distance > size` is outside it
+        let distance = grain.size + crystal_distance(adjusted_distance, grain.size, grain_center);
+        
+        if distance > grain.size {
+            return vec4<f32>(0.0, 0.0, 0.0, 0.0);
+        }
+        
+        // Calculate edge falloff for realistic grain edges
+        if distance > grain.size * 0.7 {
+            edge_alpha = (grain.size - distance) / (grain.size * 0.3);
+            edge_alpha = max(edge_alpha, 0.0);
+        }
     }
     
     // Apply opacity with some randomness for natural variation
     let noise = random(grain_center + pixel_pos * 0.1) * 0.1 - 0.05;
     let final_opacity = grain.opacity * edge_alpha * (1.0 + noise);
     
+    // Dye clouds hold back the channel their layer records
+    var color = params.grain_color.rgb;
+    if grain.dye_layer >= 0.0 {
+        color = color * params.layer_tints[u32(grain.dye_layer)].rgb;
+    }
+    
     return vec4<f32>(
-        params.grain_color.rgb,
+        color,
         clamp(final_opacity, 0.0, 1.0)
     );
 }
@@ -1,5 +1,5 @@
 use super::GpuGrainRenderer;
-use crate::{FilmStock, GrainParams, Grain};
+use crate::{dye_layers, get_coupler_spread, get_crystal_shape, FilmStock, GrainParams, Grain};
 use wgpu::util::DeviceExt;
 use bytemuck::{Pod, Zeroable};
 
@@ -10,7 +10,9 @@ struct GpuGrain {
     size: f32,
     opacity: f32,
     shape_factor: f32,
-    _padding: [f32; 3], // Align to 32 bytes
+    angle: f32,         // Radians; shape_factor stretches along this direction
+    dye_layer: f32,     // Index into layer_tints, or -1 for silver grain
+    _padding: f32,      // Align to 32 bytes
 }
 
 #[repr(C)]
@@ -19,8 +21,11 @@ struct GpuParams {
     width: u32,
     height: u32,
     grain_count: u32,
-    _padding: u32,
+    crystal: u32, // CrystalShape discriminant
     grain_color: [f32; 4], // RGBA
+    layer_tints: [[f32; 4]; 3], // Colour stocks: RGB multipliers of the cyan, magenta and yellow layers
+    coupler_spread: f32, // Dye cloud width relative to the grain for chromogenic stocks, 0 for silver
+    _padding: [f32; 3],
 }
 
 pub struct WgpuGrainRenderer {
@@ -133,7 +138,9 @@ impl WgpuGrainRenderer {
             size: grain.size,
             opacity: grain.opacity,
             shape_factor: grain.shape_factor,
-            _padding: [0.0; 3],
+            angle: grain.angle,
+            dye_layer: grain.dye_layer.map_or(-1.0, f32::from),
+            _padding: 0.0,
         }).collect()
     }
 
@@ -167,8 +174,14 @@ impl GpuGrainRenderer for WgpuGrainRenderer {
             width: params.width,
             height: params.height,
             grain_count: grains.len() as u32,
-            _padding: 0,
+            crystal: get_crystal_shape(&stock.grain_structure) as u32,
             grain_color: self.get_grain_color(stock),
+            layer_tints: std::array::from_fn(|layer| {
+                let [r, g, b] = dye_layers::layer_tint(layer as u8);
+                [r, g, b, 1.0]
+            }),
+            coupler_spread: get_coupler_spread(&stock.basic_info.process).unwrap_or(0.0),
+            _padding: [0.0; 3],
         };
 
         let params_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -0,0 +1,222 @@
+use image::RgbaImage;
+use rayon::prelude::*;
+
+use crate::color_response::luminance;
+use crate::{film_format, FilmStock, GrainParams};
+
+// Light reflected back off the film base spreads about twice the base's thickness
+const HALATION_SPREAD_MICRONS: f32 = 250.0;
+// Canvas scale when no film format is given: a 35mm frame's long side
+const DEFAULT_FRAME_MICRONS: f32 = 36_000.0;
+// Only tones above this luminance are bright enough to reach the base and back
+const HIGHLIGHT_THRESHOLD: f32 = 0.75;
+// Box blurs repeated this many times approximate a Gaussian
+const BLUR_PASSES: usize = 3;
+// Columns per rayon task in the vertical pass
+const COLUMN_CHUNK: usize = 64;
+
+// Remjet-free stocks glow this much, and only they glow unless the strength is set
+const STRONG_HALATION: f32 = 1.0;
+
+// Glow the stock adds around a photo's highlights
+#[derive(Debug, Clone, Copy)]
+pub struct Halation {
+    pub strength: f32,
+    pub sigma_px: f32,
+    pub color: [f32; 3],
+}
+
+impl Halation {
+    // None when the stock's anti-halation backing leaves nothing visible or the strength is off.
+    // Without a strength only remjet-free stocks glow; setting one opts every stock in.
+    pub fn for_params(params: &GrainParams, stock: &FilmStock) -> Result<Option<Self>, String> {
+        let described = halation_strength(&stock.special_effects.halation);
+        let strength = match params.halation_strength {
+            Some(multiplier) => described * multiplier,
+            None if described >= STRONG_HALATION => described,
+            None => 0.0,
+        };
+        if strength <= 0.0 {
+            return Ok(None);
+        }
+
+        let long_side = params.width.max(params.height).max(1) as f32;
+        let microns_per_pixel = film_format::microns_per_pixel(params)?.unwrap_or(DEFAULT_FRAME_MICRONS / long_side);
+        Ok(Some(Self {
+            strength,
+            sigma_px: (HALATION_SPREAD_MICRONS * stock.special_effects.halation_radius / microns_per_pixel).max(0.5),
+            color: parse_hex_color(&stock.special_effects.halation_color).unwrap_or([1.0; 3]),
+        }))
+    }
+}
+
+// How much halation a stock shows, from its description: remjet-free stocks glow fully,
+// stocks with an anti-halation layer only faintly
+pub fn halation_strength(halation: &str) -> f32 {
+    let halation = halation.to_lowercase();
+    if halation.contains("strong") {
+        STRONG_HALATION
+    } else if halation.contains("moderate") {
+        0.3
+    } else if halation.contains("very low") || halation.contains("minimal") || halation.contains("reduced") {
+        0.05
+    } else if halation.contains("low") {
+        0.12
+    } else if halation.is_empty() || halation.contains("none") {
+        0.0
+    } else {
+        0.3
+    }
+}
+
+// "#rrggbb" as 0-1 floats
+pub fn parse_hex_color(hex: &str) -> Option<[f32; 3]> {
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok().map(|v| v as f32 / 255.0);
+    Some([channel(0)?, channel(1)?, channel(2)?])
+}
+
+// Thresholds the photo's highlights, spreads them like light scattered back from the base,
+// and screens the tinted glow over the photo
+pub fn apply(photo: &mut RgbaImage, halation: &Halation) {
+    let (width, height) = (photo.width() as usize, photo.height() as usize);
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    let mut glow: Vec<f32> = photo
+        .as_raw()
+        .par_chunks_exact(4)
+        .map(|pixel| {
+            let luminance = luminance([pixel[0], pixel[1], pixel[2]].map(|c| c as f32 / 255.0));
+            ((luminance - HIGHLIGHT_THRESHOLD) / (1.0 - HIGHLIGHT_THRESHOLD)).max(0.0)
+        })
+        .collect();
+    if glow.iter().all(|&g| g == 0.0) {
+        return;
+    }
+    gaussian_blur(&mut glow, width, height, halation.sigma_px);
+
+    let tint = halation.color.map(|c| c * halation.strength);
+    photo.as_mut().par_chunks_exact_mut(4).zip(glow.par_iter()).for_each(|(pixel, &glow)| {
+        for (channel, tint) in pixel.iter_mut().zip(tint) {
+            let light = (glow * tint).min(1.0);
+            let base = *channel as f32 / 255.0;
+            *channel = ((1.0 - (1.0 - base) * (1.0 - light)) * 255.0).round() as u8;
+        }
+    });
+}
+
+// Repeated box blurs of the radius whose variance sums to sigma²
+pub fn gaussian_blur(plane: &mut [f32], width: usize, height: usize, sigma: f32) {
+    let box_width = (12.0 * sigma * sigma / BLUR_PASSES as f32 + 1.0).sqrt();
+    let radius = ((box_width - 1.0) * 0.5).round().max(1.0) as usize;
+    let mut scratch = vec![0.0; plane.len()];
+    for _ in 0..BLUR_PASSES {
+        plane.par_chunks_exact(width).zip(scratch.par_chunks_exact_mut(width)).for_each(|(row, out)| box_blur_row(row, out, radius));
+        box_blur_columns(&scratch, plane, width, height, radius);
+    }
+}
+
+// Mean of the 2r+1 neighbours along the row, edges extended
+fn box_blur_row(row: &[f32], out: &mut [f32], radius: usize) {
+    let last = row.len() - 1;
+    let at = |i: isize| row[i.clamp(0, last as isize) as usize];
+    let scale = 1.0 / (2 * radius + 1) as f32;
+    let mut sum: f32 = (-(radius as isize)..=radius as isize).map(at).sum();
+    for (x, value) in out.iter_mut().enumerate() {
+        *value = sum * scale;
+        sum += at((x + radius + 1) as isize) - at(x as isize - radius as isize);
+    }
+}
+
+// The same down each column, keeping running sums for a chunk of columns at a time so
+// every read walks along a row
+fn box_blur_columns(plane: &[f32], out: &mut [f32], width: usize, height: usize, radius: usize) {
+    let scale = 1.0 / (2 * radius + 1) as f32;
+    let row = |y: isize| y.clamp(0, height as isize - 1) as usize * width;
+    let chunks: Vec<(usize, usize)> = (0..width).step_by(COLUMN_CHUNK).map(|start| (start, (start + COLUMN_CHUNK).min(width))).collect();
+    let results: Vec<Vec<f32>> = chunks
+        .par_iter()
+        .map(|&(start, end)| {
+            let mut sums = vec![0.0; end - start];
+            for y in -(radius as isize)..=radius as isize {
+                let offset = row(y);
+                sums.iter_mut().zip(&plane[offset + start..offset + end]).for_each(|(sum, v)| *sum += v);
+            }
+            let mut column_out = Vec::with_capacity((end - start) * height);
+            for y in 0..height as isize {
+                column_out.extend(sums.iter().map(|sum| sum * scale));
+                let (add, remove) = (row(y + radius as isize + 1), row(y - radius as isize));
+                for (i, sum) in sums.iter_mut().enumerate() {
+                    *sum += plane[add + start + i] - plane[remove + start + i];
+                }
+            }
+            column_out
+        })
+        .collect();
+    for ((start, end), column_out) in chunks.into_iter().zip(results) {
+        let chunk_width = end - start;
+        for (y, values) in column_out.chunks_exact(chunk_width).enumerate() {
+            out[y * width + start..y * width + end].copy_from_slice(values);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_remjet_free_stocks_glow_unless_asked() {
+        let stocks = crate::load_film_stock_data().unwrap();
+        let mut params: GrainParams = serde_json::from_str(concat!(
+            r#"{"film_stock":"Kodak Portra 400","exposure_compensation":0,"size_multiplier":1,"contrast":100,"#,
+            r#""grain_density":1000,"width":640,"height":480,"background":"transparent"}"#
+        ))
+        .unwrap();
+        let (cinestill, portra) = (&stocks["CineStill 800T"], &stocks["Kodak Portra 400"]);
+        assert!(Halation::for_params(&params, cinestill).unwrap().is_some());
+        assert!(Halation::for_params(&params, portra).unwrap().is_none());
+
+        params.halation_strength = Some(1.0);
+        assert!(Halation::for_params(&params, portra).unwrap().is_some_and(|halation| halation.strength < STRONG_HALATION));
+        params.halation_strength = Some(0.0);
+        assert!(Halation::for_params(&params, cinestill).unwrap().is_none());
+    }
+
+    #[test]
+    fn blurring_keeps_the_light() {
+        let (width, height) = (90, 70);
+        let mut plane = vec![0.0; width * height];
+        plane[35 * width + 45] = 100.0;
+        gaussian_blur(&mut plane, width, height, 6.0);
+        assert!((plane.iter().sum::<f32>() - 100.0).abs() < 0.01);
+        assert!(plane[35 * width + 45] > plane[35 * width + 55] && plane[35 * width + 55] > plane[35 * width + 65]);
+    }
+
+    #[test]
+    fn highlights_glow_in_the_halation_colour() {
+        let mut photo = RgbaImage::from_pixel(60, 60, image::Rgba([40, 40, 40, 255]));
+        for y in 28..32 {
+            for x in 28..32 {
+                photo.put_pixel(x, y, image::Rgba([255, 255, 255, 255]));
+            }
+        }
+        let dark = photo.clone();
+        let halation = Halation { strength: 1.0, sigma_px: 4.0, color: parse_hex_color("#ff5a1f").unwrap() };
+        apply(&mut photo, &halation);
+
+        let near = photo.get_pixel(35, 30);
+        assert!(near[0] > 40 && near[0] > near[1] && near[1] > near[2], "{:?}", near);
+        assert_eq!(photo.get_pixel(2, 2), dark.get_pixel(2, 2));
+
+        // Without highlights nothing changes
+        let mut flat = RgbaImage::from_pixel(20, 20, image::Rgba([150, 120, 90, 255]));
+        apply(&mut flat, &halation);
+        assert!(flat.pixels().all(|p| p.0 == [150, 120, 90, 255]));
+    }
+}
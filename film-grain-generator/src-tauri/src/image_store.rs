@@ -0,0 +1,106 @@
+use image::{DynamicImage, ImageDecoder, ImageReader, RgbaImage};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri_plugin_fs::FsExt;
+
+use crate::render_cache::encode_preview;
+
+// User photos are decoded once, by path, and stay in the backend. The webview gets a
+// thumbnail to draw behind the grain; compositing uses the full-resolution buffer here.
+const MAX_STORED_IMAGES: usize = 2;
+const THUMBNAIL_MAX_EDGE: u32 = 2048; // Largest canvas the UI offers
+
+pub struct StoredImage {
+    pub path: PathBuf,
+    pub image: Arc<RgbaImage>,
+    thumbnail: Arc<RgbaImage>,
+}
+
+#[derive(Default)]
+pub struct ImageStore {
+    next_id: AtomicU64,
+    images: Mutex<VecDeque<(u64, Arc<StoredImage>)>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OpenedImage {
+    image_id: u64,
+    file_name: String,
+    width: u32,
+    height: u32,
+}
+
+impl ImageStore {
+    pub fn insert(&self, image: StoredImage) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut images = self.images.lock().unwrap_or_else(|e| e.into_inner());
+
+        images.push_back((id, Arc::new(image)));
+        while images.len() > MAX_STORED_IMAGES {
+            images.pop_front();
+        }
+        id
+    }
+
+    pub fn get(&self, id: u64) -> Result<Arc<StoredImage>, String> {
+        let images = self.images.lock().unwrap_or_else(|e| e.into_inner());
+        images
+            .iter()
+            .find(|(image_id, _)| *image_id == id)
+            .map(|(_, image)| image.clone())
+            .ok_or_else(|| format!("Image {} is no longer loaded - open it again", id))
+    }
+}
+
+// Decodes with the EXIF orientation applied, as the browser did when it loaded photos
+pub fn load_image(path: &Path) -> Result<StoredImage, String> {
+    let mut decoder = ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|e| format!("Failed to open {:?}: {}", path, e))?
+        .into_decoder()
+        .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let orientation = decoder.orientation().map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+
+    let mut image = DynamicImage::from_decoder(decoder).map_err(|e| format!("Failed to decode {:?}: {}", path, e))?;
+    image.apply_orientation(orientation);
+
+    let thumbnail = if image.width().max(image.height()) > THUMBNAIL_MAX_EDGE {
+        image.thumbnail(THUMBNAIL_MAX_EDGE, THUMBNAIL_MAX_EDGE).to_rgba8()
+    } else {
+        image.to_rgba8()
+    };
+
+    Ok(StoredImage {
+        path: path.to_path_buf(),
+        image: Arc::new(image.into_rgba8()),
+        thumbnail: Arc::new(thumbnail),
+    })
+}
+
+#[tauri::command]
+pub async fn open_user_image(app: tauri::AppHandle, store: tauri::State<'_, ImageStore>, path: String) -> Result<OpenedImage, String> {
+    let path = PathBuf::from(path);
+    // Only files the user picked in the open dialog (which grants them to the fs scope)
+    if !app.fs_scope().is_allowed(&path) {
+        return Err(format!("{:?} was not chosen through the open dialog", path));
+    }
+
+    println!("Loading user image: {:?}", path);
+    let stored = load_image(&path)?;
+    let (width, height) = stored.image.dimensions();
+    println!("Loaded image: {}x{} pixels", width, height);
+
+    let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let image_id = store.insert(stored);
+    Ok(OpenedImage { image_id, file_name, width, height })
+}
+
+// Binary PNG, like the grain preview
+#[tauri::command]
+pub async fn get_image_thumbnail(store: tauri::State<'_, ImageStore>, image_id: u64) -> Result<tauri::ipc::Response, String> {
+    let stored = store.get(image_id)?;
+    Ok(tauri::ipc::Response::new(encode_preview(&stored.thumbnail)?))
+}
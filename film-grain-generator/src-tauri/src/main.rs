@@ -1,14 +1,32 @@
 use serde::{Deserialize, Serialize};
-use image::{ImageBuffer, Rgba, RgbaImage, DynamicImage, ImageFormat};
+use image::{ImageBuffer, RgbaImage};
 use rand::prelude::*;
-use rayon::prelude::*;
 use std::collections::HashMap;
-use std::io::Cursor;
-use base64::{Engine as _, engine::general_purpose};
+use characteristic_curve::{CharacteristicCurve, PhotoTones};
+use color_response::{ColorResponse, GrainColorSource};
+use development::{Development, DevelopmentResponse};
+use dye_layers::DyeLayers;
+use exposure::{Exposure, ExposureResponse};
 // SIMD optimizations (using built-in CPU vectorization)
 
 #[cfg(feature = "gpu-acceleration")]
 mod gpu;
+mod boolean_model;
+mod characteristic_curve;
+mod color_response;
+mod development;
+mod dye_layers;
+mod exposure;
+mod film_format;
+mod halation;
+mod image_store;
+mod metadata;
+mod motion_effects;
+mod output;
+mod presets;
+mod raster;
+mod render_cache;
+mod tiled;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct FilmStock {
@@ -16,6 +34,12 @@ struct FilmStock {
     grain_structure: GrainStructure,
     size_metrics: SizeMetrics,
     visual_properties: VisualProperties,
+    #[serde(default)]
+    characteristic_curve: CharacteristicCurve, // 🆕 Density against log exposure
+    #[serde(default)]
+    development_response: DevelopmentResponse, // 🆕 How push/pull development changes the grain
+    #[serde(default)]
+    dye_layers: DyeLayers, // 🆕 Per-layer grain of colour stocks; unused for black-and-white
     color_properties: ColorProperties,
     special_effects: SpecialEffects,
     algorithmic_data: AlgorithmicData,
@@ -27,6 +51,8 @@ struct BasicInfo {
     iso: u32,
     #[serde(rename = "type")]
     film_type: String,
+    #[serde(default)]
+    process: String, // 🆕 "B&W" silver, or the chromogenic "C-41", "E-6" and "ECN-2"
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -132,7 +158,7 @@ struct VariationData {
     notes: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct GrainParams {
     film_stock: String,
     exposure_compensation: f32,
@@ -144,15 +170,39 @@ struct GrainParams {
     background: String,
     film_age_years: Option<f32>,  // 0-10 years
     storage_temp: Option<f32>,    // -18 to 25°C
+    seed: Option<u64>,            // Fixed seed for reproducible grain, random when absent
+    film_format: Option<String>,  // 🆕 Physical frame ("35mm", "6x7", ...); pixel-based scale when absent
+    scan_dpi: Option<f32>,        // 🆕 Scan resolution; without it the frame fills the canvas
+    #[serde(default)]
+    render_mode: RenderMode,      // 🆕 How grain is drawn; stamped when absent
+    push_pull_stops: Option<f32>, // 🆕 Development push (+) or pull (-) in stops; box speed when absent
+    developer: Option<String>,    // 🆕 "standard", "fine-grain", "solvent" or "high-acutance"
+    agitation: Option<String>,    // 🆕 "stand", "gentle", "normal" or "vigorous"
+    shutter_time: Option<f32>,    // 🆕 Seconds; exposures past the stock's threshold suffer reciprocity failure
+    #[serde(default)]
+    grain_color_source: GrainColorSource, // 🆕 Photo composites: grain colour from the stock or the photo
+    halation_strength: Option<f32>, // 🆕 Photo composites: multiplies every stock's halation; remjet-free stocks only when absent
+    bloom_strength: Option<f32>,    // 🆕 Photo composites: highlight diffusion, off when absent
+    gate_weave: Option<f32>,        // 🆕 Photo composites: multiplies the format's gate weave, off when absent
+    frame_index: Option<u32>,       // 🆕 Frame of a sequence: fresh grain and the gate weave's place; 0 when absent
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RenderMode {
+    #[default]
+    Stamped, // Translucent grain shapes composited over the image
+    Boolean, // Newson et al.'s Boolean model, driven by the image's tone
 }
 
 #[derive(Debug, Serialize)]
 struct GrainResult {
-    data: Vec<u8>,
+    render_id: u64, // Fetch pixels with get_render_preview, save with save_grain_image
     width: u32,
     height: u32,
     generation_time_ms: u128,
     grain_count: usize,
+    seed: u64,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -161,7 +211,9 @@ struct Grain {
     y: f32,
     size: f32,
     opacity: f32,
-    shape_factor: f32,
+    shape_factor: f32, // Stretch along the grain's own x axis
+    angle: f32,        // 🆕 Rotation of that axis from the canvas x axis, in radians
+    dye_layer: Option<u8>, // 🆕 Colour stocks: cyan, magenta or yellow layer (0-2); None for silver
 }
 
 #[cfg(feature = "gpu-acceleration")]
@@ -178,36 +230,51 @@ async fn try_gpu_render(grains: &[Grain], params: &GrainParams, stock: &FilmStoc
 }
 
 #[tauri::command]
-async fn generate_grain(params: GrainParams) -> Result<GrainResult, String> {
+async fn generate_grain(cache: tauri::State<'_, render_cache::RenderCache>, mut params: GrainParams) -> Result<GrainResult, String> {
     let start_time = std::time::Instant::now();
     
-    // Load film stock data (in a real app, this would be loaded once at startup)
-    let film_stocks = load_film_stock_data()?;
-    let stock = film_stocks.get(&params.film_stock)
-        .ok_or_else(|| format!("Film stock '{}' not found", params.film_stock))?;
+    // Resolve the seed up front so the result can report it and be reproduced later
+    let seed = params.seed.unwrap_or_else(random_seed);
+    params.seed = Some(seed);
     
-    // Load enhanced film data for realistic effects
-    let enhanced_data = load_enhanced_film_data()?;
-    let enhanced_stock = enhanced_data.get(&params.film_stock);
+    let (image, grain_count) = render_grain_image(&params, None).await?;
     
-    // Load variation data for authentic grain variation
-    let variation_data = load_variation_data()?;
-    let variation_stock = variation_data.get(&params.film_stock);
+    let generation_time = start_time.elapsed().as_millis();
     
-    // Generate grains using advanced algorithms with enhancements
-    let _start_time = std::time::Instant::now();
-    let mut grains = generate_grains_advanced(stock, &params, variation_stock)?;
-    let _generation_time = _start_time.elapsed();
+    let (width, height) = (params.width, params.height);
+    let render_id = cache.insert(image, params);
     
-    // Apply enhanced realistic effects
-    if let Some(enhanced) = enhanced_stock {
-        apply_enhanced_effects(&mut grains, &params, enhanced)?;
+    Ok(GrainResult {
+        render_id,
+        width,
+        height,
+        generation_time_ms: generation_time,
+        grain_count,
+        seed,
+    })
+}
+
+// Renders the grain layer for params (seed already resolved), returning it with its grain count.
+// Over a photo, each grain takes the size its tone on the curve develops.
+async fn render_grain_image(params: &GrainParams, photo: Option<&PhotoTones>) -> Result<(RgbaImage, usize), String> {
+    // 🆕 The Boolean model renders its own layer, without stamps or GPU
+    if params.render_mode == RenderMode::Boolean {
+        return boolean_model::render_layer(params);
     }
     
-    // 🆕 ENHANCEMENT 14: Apply halation effects for CineStill films
-    apply_halation_effect(&mut grains, stock, &params);
+    // 🆕 Huge canvases go tile by tile instead of through one giant pixel list
+    if tiled::should_tile(params.width, params.height) {
+        return tiled::render_tiled_image(params, photo);
+    }
+    
+    let (stock, mut grains) = prepare_grains(params)?;
+    if let Some(photo) = photo {
+        photo.scale_grains(&mut grains);
+    }
+    let stock = &stock;
     
     // Smart rendering strategy:
+
     // - CPU (Rayon + SIMD): Fast for normal workloads (0-50K grains) - no GPU overhead
     // - GPU: Only for massive workloads (>50K grains) where parallelism outweighs overhead
     // This prevents the "slow/white screen" issues you experienced with GPU on small workloads
@@ -215,33 +282,90 @@ async fn generate_grain(params: GrainParams) -> Result<GrainResult, String> {
         // Try GPU for massive workloads only
         #[cfg(feature = "gpu-acceleration")]
         {
-            match try_gpu_render(&grains, &params, stock).await {
+            match try_gpu_render(&grains, params, stock).await {
                 Ok(data) => {
                     println!("🚀 Used GPU acceleration for {} grains", grains.len());
                     data
                 },
                 Err(e) => {
                     println!("⚠️ GPU failed ({}), falling back to optimized CPU", e);
-                    render_grains_parallel(&grains, &params, stock)?
+                    render_grains_parallel(&grains, params, stock)?
                 }
             }
         }
         #[cfg(not(feature = "gpu-acceleration"))]
-        render_grains_parallel(&grains, &params, stock)?
+        render_grains_parallel(&grains, params, stock)?
     } else {
         // Use optimized CPU rendering for normal workloads (much faster for <50K grains)
-        render_grains_parallel(&grains, &params, stock)?
+        render_grains_parallel(&grains, params, stock)?
     };
     
-    let generation_time = start_time.elapsed().as_millis();
+    let image = RgbaImage::from_raw(params.width, params.height, image_data)
+        .ok_or("Rendered buffer does not match the canvas size")?;
+    Ok((image, grains.len()))
+}
+
+// Builds the full grain list for a render. Everything random is drawn from one
+// seeded generator, so the same params (including seed) always give the same grain.
+fn prepare_grains(params: &GrainParams) -> Result<(FilmStock, Vec<Grain>), String> {
+    let data = StockData::load(&params.film_stock)?;
+    let grains = generate_stock_grains(&data, params)?;
+    Ok((data.stock, grains))
+}
+
+// Everything known about one (possibly blended) stock, loaded once per render
+struct StockData {
+    stock: FilmStock,
+    enhanced: Option<EnhancedFilmData>,
+    variation: Option<VariationData>,
+    exposure: ExposureResponse,
+}
+
+impl StockData {
+    fn load(film_stock: &str) -> Result<Self, String> {
+        // Load film stock data (in a real app, this would be loaded once at startup)
+        let film_stocks = load_film_stock_data()?;
+        let stock = resolve_film_stock(&film_stocks, film_stock)?;
+        
+        // Load enhanced film data for realistic effects
+        let enhanced_data = load_enhanced_film_data()?;
+        let enhanced = resolve_enhanced_film_data(&enhanced_data, film_stock);
+        
+        // Load variation data for authentic grain variation
+        let variation_data = load_variation_data()?;
+        let variation = resolve_variation_data(&variation_data, film_stock);
+        
+        // Grain across under- and overexposure
+        let exposure = resolve_image_response(film_stock)?.exposure_response;
+        
+        Ok(Self { stock, enhanced, variation, exposure })
+    }
+}
+
+fn generate_stock_grains(data: &StockData, params: &GrainParams) -> Result<Vec<Grain>, String> {
+    let mut rng = StdRng::seed_from_u64(motion_effects::grain_seed(params));
     
-    Ok(GrainResult {
-        data: image_data,
-        width: params.width,
-        height: params.height,
-        generation_time_ms: generation_time,
-        grain_count: grains.len(),
-    })
+    // Generate grains using advanced algorithms with enhancements
+    let mut grains = if data.stock.basic_info.film_type == "color" {
+        // 🆕 Each dye layer is its own grain field, with its own seed, size and share of grains
+        let mut grains = Vec::new();
+        for (index, layer) in data.stock.dye_layers.layers().iter().enumerate() {
+            let layer_params = dye_layers::layer_params(params, layer, index);
+            let mut layer_rng = StdRng::seed_from_u64(layer_params.seed.unwrap_or(0));
+            let layer_grains = generate_grains_advanced(&data.stock, &layer_params, data.variation.as_ref(), &data.exposure, &mut layer_rng)?;
+            grains.extend(layer_grains.into_iter().map(|grain| Grain { dye_layer: Some(index as u8), ..grain }));
+        }
+        grains
+    } else {
+        generate_grains_advanced(&data.stock, params, data.variation.as_ref(), &data.exposure, &mut rng)?
+    };
+    
+    // Apply enhanced realistic effects
+    if let Some(enhanced) = &data.enhanced {
+        apply_enhanced_effects(&mut grains, params, enhanced, &mut rng)?;
+    }
+    
+    Ok(grains)
 }
 
 fn load_enhanced_film_data() -> Result<HashMap<String, EnhancedFilmData>, String> {
@@ -251,6 +375,43 @@ fn load_enhanced_film_data() -> Result<HashMap<String, EnhancedFilmData>, String
     Ok(parsed)
 }
 
+// 🆕 A stock's entry in imagecolors.json: its grain across exposure and in each colour
+#[derive(Debug, Clone, Default, Deserialize)]
+struct ImageResponse {
+    exposure_response: ExposureResponse,
+    color_response: ColorResponse,
+}
+
+fn load_image_responses() -> Result<HashMap<String, ImageResponse>, String> {
+    let image_data = include_str!("../../imagecolors.json");
+    serde_json::from_str(image_data).map_err(|e| format!("Failed to parse imagecolors.json: {}", e))
+}
+
+// A stock's image response, blended for "A + B @ t" names, or the defaults for unknown
+// stocks. The file is parsed once per thread.
+fn resolve_image_response(name: &str) -> Result<ImageResponse, String> {
+    IMAGE_RESPONSES.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if cache.is_none() {
+            *cache = Some(load_image_responses()?);
+        }
+        let data = cache.as_ref().expect("image responses loaded above");
+        if let Some(response) = data.get(name) {
+            return Ok(response.clone());
+        }
+        
+        let Some(blend) = parse_stock_blend(name) else { return Ok(ImageResponse::default()) };
+        Ok(match (data.get(&blend.from), data.get(&blend.to)) {
+            (Some(from), Some(to)) => ImageResponse {
+                exposure_response: ExposureResponse::blend(&from.exposure_response, &to.exposure_response, blend.weight),
+                color_response: ColorResponse::blend(&from.color_response, &to.color_response, blend.weight),
+            },
+            (Some(only), None) | (None, Some(only)) => only.clone(),
+            (None, None) => ImageResponse::default(),
+        })
+    })
+}
+
 fn load_variation_data() -> Result<HashMap<String, VariationData>, String> {
     let variation_data = include_str!("../../variation.json");
     let parsed: HashMap<String, VariationData> = serde_json::from_str(variation_data)
@@ -286,6 +447,7 @@ fn load_film_stock_data() -> Result<HashMap<String, FilmStock>, String> {
             name: "Kodak Tri-X 400".to_string(),
             iso: 400,
             film_type: "bw".to_string(),
+            process: "B&W".to_string(),
         },
         grain_structure: GrainStructure {
             crystal_type: "cubic".to_string(),
@@ -313,6 +475,9 @@ fn load_film_stock_data() -> Result<HashMap<String, FilmStock>, String> {
             shadow_visibility: "high".to_string(),
             midtone_prominence: "medium".to_string(),
         },
+        characteristic_curve: CharacteristicCurve::from_descriptions("bw", "high", "high", "low"),
+        development_response: DevelopmentResponse::from_crystal_type("cubic", "bw"),
+        dye_layers: DyeLayers::default(),
         color_properties: ColorProperties {
             primary_cast: "neutral".to_string(),
             rgb_ranges: vec![RgbRange {
@@ -414,6 +579,7 @@ fn load_film_stock_data() -> Result<HashMap<String, FilmStock>, String> {
     cinestill800t.size_metrics.density_per_mm2 = 600000;
     cinestill800t.size_metrics.avg_size_um = 1.4;
     cinestill800t.special_effects.halation = "strong".to_string();
+    cinestill800t.special_effects.halation_color = REMJET_FREE_HALATION_COLOR.to_string();
     cinestill800t.color_properties.rgb_ranges = vec![
         RgbRange { r: vec![200, 255], g: vec![180, 220], b: vec![220, 255], weight: 1.0 }
     ];
@@ -424,23 +590,255 @@ fn load_film_stock_data() -> Result<HashMap<String, FilmStock>, String> {
     Ok(stocks)
 }
 
-fn generate_grains_advanced(stock: &FilmStock, params: &GrainParams, variation_data: Option<&VariationData>) -> Result<Vec<Grain>, String> {
-    let mut rng = thread_rng();
+// 🆕 Blended film stocks: "Kodak Portra 160 + Kodak Portra 400 @ 0.35" means 35% of the way
+// from Portra 160 towards Portra 400. Accepted anywhere a single stock name is.
+const STOCK_BLEND_SEPARATOR: &str = " + ";
+const STOCK_BLEND_WEIGHT_SEPARATOR: &str = " @ ";
+
+#[derive(Debug, Clone, PartialEq)]
+struct StockBlend {
+    from: String,
+    to: String,
+    weight: f32, // 0.0 = pure `from`, 1.0 = pure `to`
+}
+
+fn parse_stock_blend(name: &str) -> Option<StockBlend> {
+    let (stocks, weight) = match name.rsplit_once(STOCK_BLEND_WEIGHT_SEPARATOR) {
+        Some((stocks, weight)) => (stocks, weight.trim().parse::<f32>().ok()?),
+        None => (name, 0.5), // Even mix when no weight is given
+    };
+    let (from, to) = stocks.split_once(STOCK_BLEND_SEPARATOR)?;
+    
+    Some(StockBlend {
+        from: from.trim().to_string(),
+        to: to.trim().to_string(),
+        weight: weight.clamp(0.0, 1.0),
+    })
+}
+
+fn stock_blend_name(from: &str, to: &str, weight: f32) -> String {
+    format!("{}{}{}{}{:.2}", from, STOCK_BLEND_SEPARATOR, to, STOCK_BLEND_WEIGHT_SEPARATOR, weight.clamp(0.0, 1.0))
+}
+
+fn resolve_film_stock(stocks: &HashMap<String, FilmStock>, name: &str) -> Result<FilmStock, String> {
+    if let Some(stock) = stocks.get(name) {
+        return Ok(stock.clone());
+    }
+    
+    let blend = parse_stock_blend(name)
+        .ok_or_else(|| format!("Film stock '{}' not found", name))?;
+    let from = stocks.get(&blend.from)
+        .ok_or_else(|| format!("Film stock '{}' not found", blend.from))?;
+    let to = stocks.get(&blend.to)
+        .ok_or_else(|| format!("Film stock '{}' not found", blend.to))?;
+    
+    let mut blended = blend_film_stocks(from, to, blend.weight);
+    blended.basic_info.name = name.to_string();
+    Ok(blended)
+}
+
+fn resolve_enhanced_film_data(data: &HashMap<String, EnhancedFilmData>, name: &str) -> Option<EnhancedFilmData> {
+    if let Some(enhanced) = data.get(name) {
+        return Some(enhanced.clone());
+    }
+    
+    let blend = parse_stock_blend(name)?;
+    match (data.get(&blend.from), data.get(&blend.to)) {
+        (Some(from), Some(to)) => Some(blend_enhanced_film_data(from, to, blend.weight)),
+        (Some(only), None) | (None, Some(only)) => Some(only.clone()),
+        (None, None) => None,
+    }
+}
+
+fn resolve_variation_data(data: &HashMap<String, VariationData>, name: &str) -> Option<VariationData> {
+    if let Some(variation) = data.get(name) {
+        return Some(variation.clone());
+    }
+    
+    let blend = parse_stock_blend(name)?;
+    match (data.get(&blend.from), data.get(&blend.to)) {
+        (Some(from), Some(to)) => Some(VariationData {
+            size_variation_coeff: lerp(from.size_variation_coeff, to.size_variation_coeff, blend.weight),
+            opacity_variation: lerp(from.opacity_variation, to.opacity_variation, blend.weight),
+            notes: format!("Blend of {} and {}", blend.from, blend.to),
+        }),
+        (Some(only), None) | (None, Some(only)) => Some(only.clone()),
+        (None, None) => None,
+    }
+}
+
+#[inline]
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn lerp_vec(a: &[f32], b: &[f32], t: f32) -> Vec<f32> {
+    if a.len() != b.len() {
+        return if t < 0.5 { a.to_vec() } else { b.to_vec() };
+    }
+    a.iter().zip(b).map(|(&x, &y)| lerp(x, y, t)).collect()
+}
+
+fn lerp_u8_range(a: &[u8], b: &[u8], t: f32) -> Vec<u8> {
+    if a.len() != b.len() {
+        return if t < 0.5 { a.to_vec() } else { b.to_vec() };
+    }
+    a.iter().zip(b).map(|(&x, &y)| lerp(x as f32, y as f32, t).round().clamp(0.0, 255.0) as u8).collect()
+}
+
+// Categorical fields can't be averaged, so take them from whichever stock dominates the mix
+fn pick(a: &str, b: &str, t: f32) -> String {
+    if t < 0.5 { a.to_string() } else { b.to_string() }
+}
+
+fn blend_film_stocks(a: &FilmStock, b: &FilmStock, t: f32) -> FilmStock {
+    let t = t.clamp(0.0, 1.0);
+    
+    // Interpolate ISO geometrically - a stop halfway between 100 and 400 is 200, not 250
+    let iso = ((a.basic_info.iso.max(1) as f32).ln() * (1.0 - t) + (b.basic_info.iso.max(1) as f32).ln() * t).exp().round() as u32;
+    
+    let rgb_ranges = if a.color_properties.rgb_ranges.len() == b.color_properties.rgb_ranges.len() {
+        a.color_properties.rgb_ranges.iter().zip(&b.color_properties.rgb_ranges)
+            .map(|(ra, rb)| RgbRange {
+                r: lerp_u8_range(&ra.r, &rb.r, t),
+                g: lerp_u8_range(&ra.g, &rb.g, t),
+                b: lerp_u8_range(&ra.b, &rb.b, t),
+                weight: lerp(ra.weight, rb.weight, t),
+            })
+            .collect()
+    } else if t < 0.5 {
+        a.color_properties.rgb_ranges.clone()
+    } else {
+        b.color_properties.rgb_ranges.clone()
+    };
+    
+    FilmStock {
+        basic_info: BasicInfo {
+            name: stock_blend_name(&a.basic_info.name, &b.basic_info.name, t),
+            iso,
+            film_type: pick(&a.basic_info.film_type, &b.basic_info.film_type, t),
+            process: pick(&a.basic_info.process, &b.basic_info.process, t),
+        },
+        grain_structure: GrainStructure {
+            crystal_type: pick(&a.grain_structure.crystal_type, &b.grain_structure.crystal_type, t),
+            shape: pick(&a.grain_structure.shape, &b.grain_structure.shape, t),
+            aspect_ratio: lerp_vec(&a.grain_structure.aspect_ratio, &b.grain_structure.aspect_ratio, t),
+            orientation: pick(&a.grain_structure.orientation, &b.grain_structure.orientation, t),
+            clustering: pick(&a.grain_structure.clustering, &b.grain_structure.clustering, t),
+            edge_type: pick(&a.grain_structure.edge_type, &b.grain_structure.edge_type, t),
+        },
+        size_metrics: SizeMetrics {
+            min_size_um: lerp(a.size_metrics.min_size_um, b.size_metrics.min_size_um, t),
+            max_size_um: lerp(a.size_metrics.max_size_um, b.size_metrics.max_size_um, t),
+            avg_size_um: lerp(a.size_metrics.avg_size_um, b.size_metrics.avg_size_um, t),
+            size_distribution: pick(&a.size_metrics.size_distribution, &b.size_metrics.size_distribution, t),
+            size_variation_coeff: lerp(a.size_metrics.size_variation_coeff, b.size_metrics.size_variation_coeff, t),
+            density_per_mm2: lerp(a.size_metrics.density_per_mm2 as f32, b.size_metrics.density_per_mm2 as f32, t).round() as u32,
+            spacing_pattern: pick(&a.size_metrics.spacing_pattern, &b.size_metrics.spacing_pattern, t),
+        },
+        visual_properties: VisualProperties {
+            opacity_range: lerp_vec(&a.visual_properties.opacity_range, &b.visual_properties.opacity_range, t),
+            contrast_level: pick(&a.visual_properties.contrast_level, &b.visual_properties.contrast_level, t),
+            edge_definition: pick(&a.visual_properties.edge_definition, &b.visual_properties.edge_definition, t),
+            opacity_variation: lerp(a.visual_properties.opacity_variation, b.visual_properties.opacity_variation, t),
+            highlight_visibility: pick(&a.visual_properties.highlight_visibility, &b.visual_properties.highlight_visibility, t),
+            shadow_visibility: pick(&a.visual_properties.shadow_visibility, &b.visual_properties.shadow_visibility, t),
+            midtone_prominence: pick(&a.visual_properties.midtone_prominence, &b.visual_properties.midtone_prominence, t),
+        },
+        characteristic_curve: CharacteristicCurve {
+            base_fog: lerp(a.characteristic_curve.base_fog, b.characteristic_curve.base_fog, t),
+            gamma: lerp(a.characteristic_curve.gamma, b.characteristic_curve.gamma, t),
+            toe: lerp(a.characteristic_curve.toe, b.characteristic_curve.toe, t),
+            shoulder: lerp(a.characteristic_curve.shoulder, b.characteristic_curve.shoulder, t),
+            d_max: lerp(a.characteristic_curve.d_max, b.characteristic_curve.d_max, t),
+        },
+        development_response: DevelopmentResponse {
+            grain_growth_per_stop: lerp(a.development_response.grain_growth_per_stop, b.development_response.grain_growth_per_stop, t),
+            contrast_per_stop: lerp(a.development_response.contrast_per_stop, b.development_response.contrast_per_stop, t),
+            clumping_per_stop: lerp(a.development_response.clumping_per_stop, b.development_response.clumping_per_stop, t),
+            edge_effect: lerp(a.development_response.edge_effect, b.development_response.edge_effect, t),
+        },
+        dye_layers: DyeLayers::blend(&a.dye_layers, &b.dye_layers, t),
+        color_properties: ColorProperties {
+            primary_cast: pick(&a.color_properties.primary_cast, &b.color_properties.primary_cast, t),
+            rgb_ranges,
+            color_variation: pick(&a.color_properties.color_variation, &b.color_properties.color_variation, t),
+            saturation_level: pick(&a.color_properties.saturation_level, &b.color_properties.saturation_level, t),
+        },
+        special_effects: SpecialEffects {
+            halation: pick(&a.special_effects.halation, &b.special_effects.halation, t),
+            halation_color: pick(&a.special_effects.halation_color, &b.special_effects.halation_color, t),
+            halation_radius: lerp(a.special_effects.halation_radius, b.special_effects.halation_radius, t),
+            unique_artifacts: if t < 0.5 { a.special_effects.unique_artifacts.clone() } else { b.special_effects.unique_artifacts.clone() },
+            light_interaction: pick(&a.special_effects.light_interaction, &b.special_effects.light_interaction, t),
+        },
+        algorithmic_data: AlgorithmicData {
+            clustering_algorithm: pick(&a.algorithmic_data.clustering_algorithm, &b.algorithmic_data.clustering_algorithm, t),
+            distribution_function: pick(&a.algorithmic_data.distribution_function, &b.algorithmic_data.distribution_function, t),
+            spatial_correlation: lerp(a.algorithmic_data.spatial_correlation, b.algorithmic_data.spatial_correlation, t),
+            fractal_dimension: lerp(a.algorithmic_data.fractal_dimension, b.algorithmic_data.fractal_dimension, t),
+        },
+    }
+}
+
+fn blend_enhanced_film_data(a: &EnhancedFilmData, b: &EnhancedFilmData, t: f32) -> EnhancedFilmData {
+    let (ca, cb) = (&a.color_crossover, &b.color_crossover);
+    let (aa, ab) = (&a.aging_effects, &b.aging_effects);
+    let (la, lb) = (&a.clustering_data, &b.clustering_data);
+    
+    EnhancedFilmData {
+        color_crossover: ColorCrossover {
+            red_to_green: lerp(ca.red_to_green, cb.red_to_green, t),
+            red_to_blue: lerp(ca.red_to_blue, cb.red_to_blue, t),
+            green_to_red: lerp(ca.green_to_red, cb.green_to_red, t),
+            green_to_blue: lerp(ca.green_to_blue, cb.green_to_blue, t),
+            blue_to_red: lerp(ca.blue_to_red, cb.blue_to_red, t),
+            blue_to_green: lerp(ca.blue_to_green, cb.blue_to_green, t),
+        },
+        aging_effects: AgingEffects {
+            grain_increase_per_year: lerp(aa.grain_increase_per_year, ab.grain_increase_per_year, t),
+            contrast_loss_per_year: lerp(aa.contrast_loss_per_year, ab.contrast_loss_per_year, t),
+            storage_temp_factor: lerp(aa.storage_temp_factor, ab.storage_temp_factor, t),
+        },
+        clustering_data: ClusteringData {
+            fractal_dimension: lerp(la.fractal_dimension, lb.fractal_dimension, t),
+            spatial_correlation: lerp(la.spatial_correlation, lb.spatial_correlation, t),
+            cluster_probability: lerp(la.cluster_probability, lb.cluster_probability, t),
+        },
+    }
+}
+
+fn generate_grains_advanced(stock: &FilmStock, params: &GrainParams, variation_data: Option<&VariationData>, exposure_response: &ExposureResponse, rng: &mut StdRng) -> Result<Vec<Grain>, String> {
     let mut grains = Vec::new();
     
     // Use film stock's actual density as base, then apply user density multiplier
-    let canvas_area_ratio = (params.width * params.height) as f32 / (1024.0 * 1024.0);
     let stock_base_density = stock.size_metrics.density_per_mm2 as f32;
     let user_density_multiplier = params.grain_density as f32 / 1000.0; // Convert from 0.5-5.0 range
-    // Adjust divisor for better grain count
-    let final_grain_count = ((stock_base_density * canvas_area_ratio * user_density_multiplier) / 10.0) as usize; // 🚀 Even higher density
+    // 🆕 Grain count and size from the film format and scan resolution (or the old pixel scale)
+    let scale = film_format::GrainScale::for_params(params, stock, user_density_multiplier)?;
+    let final_grain_count = scale.grain_count;
     println!("Density: {:.1}x multiplier, Stock density: {}/mm², Final: {} grains for {}", 
              user_density_multiplier, stock_base_density as u32, final_grain_count, stock.basic_info.name);
+    if let Some(microns_per_pixel) = scale.microns_per_pixel {
+        println!("🎞️ Film scale: {:.2} µm/px, grain radius x{:.2} px/µm", microns_per_pixel, scale.pixels_per_micron);
+    }
+    // 🆕 Push/pull, developer and agitation
+    let development = Development::for_params(params, stock);
+    if !development.is_normal() {
+        println!("🧪 Development: grain x{:.2}, clumping x{:.2}, contrast x{:.2}, edges x{:.2}",
+                 development.grain_size, development.clumping, development.contrast, development.edge_softness);
+    }
+    // 🆕 Exposure along the stock's grain curve, less any speed lost to reciprocity failure
+    let exposure = Exposure::for_params(params, exposure_response);
+    if exposure.stops != 0.0 {
+        println!("📷 Exposure: {:+.1} stops ({:.1} lost to reciprocity), grain size x{:.2}, visibility x{:.2}",
+                 exposure.stops, exposure.reciprocity_loss, exposure.grain_size, exposure.grain_visibility);
+    }
     
     // Generate grains with spatial correlation
     // 🚀 NEW: Generate grain positions using clustering data as pattern indicator
     let pattern = if stock.grain_structure.clustering == "heavy" { "clustered" } else { "random" };
-    let grain_positions = generate_pattern_based_positions(pattern, params, final_grain_count, &mut rng);
+    let grain_positions = generate_pattern_based_positions(pattern, params, final_grain_count, rng);
     
     for (x, y) in grain_positions.iter() {
         let x = *x;
@@ -449,7 +847,8 @@ fn generate_grains_advanced(stock: &FilmStock, params: &GrainParams, variation_d
         // Use authentic variation coefficient from research data
         let size_variation_coeff = variation_data
             .map(|v| v.size_variation_coeff)
-            .unwrap_or(stock.size_metrics.size_variation_coeff); // Fallback to hardcoded
+            .unwrap_or(stock.size_metrics.size_variation_coeff) // Fallback to hardcoded
+            * development.size_spread;
             
         let size_factor = {
             let rand_val = rng.gen::<f32>();
@@ -467,9 +866,9 @@ fn generate_grains_advanced(stock: &FilmStock, params: &GrainParams, variation_d
         
         // 🚀 NEW: Use JSON size variation with shape-based adjustments
         let size_range_um = rng.gen_range(stock.size_metrics.min_size_um..=stock.size_metrics.max_size_um);
-        let shape_size_factor = get_shape_size_factor(&stock.grain_structure.shape, &mut rng);
+        let shape_size_factor = get_shape_size_factor(&stock.grain_structure.shape, rng);
         let base_size = size_range_um * 0.5 * shape_size_factor; // Apply shape-specific sizing
-        let mut size = (base_size * size_factor * params.size_multiplier).max(0.3); // Minimum 0.3 pixel
+        let mut size = (base_size * size_factor * params.size_multiplier * development.grain_size).max(0.3); // Minimum 0.3 µm radius
         
         // Use authentic opacity variation from research data
         let base_opacity = rng.gen_range(stock.visual_properties.opacity_range[0]..stock.visual_properties.opacity_range[1]);
@@ -490,12 +889,11 @@ fn generate_grains_advanced(stock: &FilmStock, params: &GrainParams, variation_d
             "low" => 0.85,
             _ => 1.0,
         };
-        let contrast_factor = user_contrast_factor * film_contrast_factor;
+        let contrast_factor = user_contrast_factor * film_contrast_factor * development.contrast;
         
-        // 🚀 ENHANCED: Apply realistic exposure compensation effects
-        let exposure_factor = apply_realistic_exposure_effects(params.exposure_compensation, &mut size, &mut rng);
+        size *= exposure.grain_size * scale.pixels_per_micron; // Radius in µm to pixels
         
-        let mut opacity = (base_opacity * contrast_factor * opacity_variation * exposure_factor).min(1.0).max(0.1);
+        let mut opacity = (base_opacity * contrast_factor * opacity_variation * exposure.grain_visibility).min(1.0).max(0.1);
         
         // 🚀 ENHANCED: Apply aging effects directly from UI parameters
         if let Some(age_years) = params.film_age_years {
@@ -534,16 +932,21 @@ fn generate_grains_advanced(stock: &FilmStock, params: &GrainParams, variation_d
         };
         
         // 🚀 NEW: Create grain with shape-based characteristics and ISO effects
-        let base_shape_factor = get_shape_factor(&stock.grain_structure.shape, &mut rng);
+        let base_shape_factor = get_shape_factor(&stock.grain_structure.shape, rng);
         let iso_irregularity = get_iso_irregularity_factor(stock.basic_info.iso);
         let shape_factor = base_shape_factor * rng.gen_range(1.0 - iso_irregularity * 0.2..1.0 + iso_irregularity * 0.2);
         
+        // 🆕 Orientation from the stock: random, or biased along its crystal alignment
+        let angle = get_grain_orientation(&stock.grain_structure.orientation, rng);
+        
         grains.push(Grain {
             x,
             y,
             size,
             opacity,
             shape_factor,
+            angle,
+            dye_layer: None,
         });
     }
     
@@ -554,18 +957,18 @@ fn generate_grains_advanced(stock: &FilmStock, params: &GrainParams, variation_d
         "light" => 0.2,
         "none" => 0.0,
         _ => 0.2,
-    };
+    } * development.clumping;
     
     if clustering_strength > 0.0 {
         // 🚀 Parse cluster size from JSON clustering field
         let cluster_size_range = parse_json_cluster_size_range(&stock.grain_structure.clustering);
-        apply_realistic_clustering(&mut grains, &mut rng, params.width, params.height, clustering_strength, cluster_size_range);
+        apply_realistic_clustering(&mut grains, rng, params.width, params.height, clustering_strength, cluster_size_range);
     }
     
     Ok(grains)
 }
 
-fn apply_realistic_clustering(grains: &mut Vec<Grain>, rng: &mut ThreadRng, width: u32, height: u32, strength: f32, cluster_size_range: (usize, usize)) {
+fn apply_realistic_clustering(grains: &mut Vec<Grain>, rng: &mut StdRng, width: u32, height: u32, strength: f32, cluster_size_range: (usize, usize)) {
     let cluster_count = (grains.len() as f32 * strength * 0.1) as usize; // 10% of grains form clusters
     
     for _ in 0..cluster_count {
@@ -593,13 +996,15 @@ fn apply_realistic_clustering(grains: &mut Vec<Grain>, rng: &mut ThreadRng, widt
                     size: seed_grain.size * rng.gen_range(0.8..1.2),
                     opacity: seed_grain.opacity * rng.gen_range(0.9..1.1),
                     shape_factor: seed_grain.shape_factor,
+                    angle: seed_grain.angle,
+                    dye_layer: seed_grain.dye_layer,
                 });
             }
         }
     }
 }
 
-fn apply_enhanced_clustering(grains: &mut Vec<Grain>, rng: &mut ThreadRng, width: u32, height: u32, clustering_type: &str) {
+fn apply_enhanced_clustering(grains: &mut Vec<Grain>, rng: &mut StdRng, width: u32, height: u32, clustering_type: &str) {
     let original_count = grains.len();
     let cluster_intensity = match clustering_type {
         "heavy" => 0.25,     // 25% of grains form clusters
@@ -638,6 +1043,8 @@ fn apply_enhanced_clustering(grains: &mut Vec<Grain>, rng: &mut ThreadRng, width
                     size: center.size * rng.gen_range(0.8..1.2),
                     opacity: center.opacity * rng.gen_range(0.9..1.1),
                     shape_factor: center.shape_factor * rng.gen_range(0.9..1.1),
+                    angle: center.angle,
+                    dye_layer: center.dye_layer,
                 });
             }
         }
@@ -651,165 +1058,46 @@ fn render_grains_parallel(grains: &[Grain], params: &GrainParams, stock: &FilmSt
     let num_threads = rayon::current_num_threads();
     println!("Rendering {} grains for {} using {} CPU threads", grains.len(), stock.basic_info.name, num_threads);
     
-    // Create image buffer
-    let mut img: RgbaImage = ImageBuffer::new(params.width, params.height);
-    
-    // Always use transparent background for the actual image data
-    for pixel in img.pixels_mut() {
-        *pixel = Rgba([0, 0, 0, 0]); // Always transparent
-    }
-    
-    // Optimize chunk size for better load balancing
-    let optimal_chunk_size = (grains.len() / (num_threads * 4)).max(100).min(1000);
-    let grain_chunks: Vec<&[Grain]> = grains.chunks(optimal_chunk_size).collect();
-    
-    // Process each chunk in parallel with pre-allocated capacity
-    let rendered_pixels: Vec<Vec<(u32, u32, Rgba<u8>)>> = grain_chunks.par_iter().map(|chunk| {
-        // Pre-allocate with estimated capacity to reduce reallocations
-        let estimated_pixels_per_grain = (std::f32::consts::PI * 4.0 * 4.0) as usize; // π*r² for average grain
-        let mut pixels = Vec::with_capacity(chunk.len() * estimated_pixels_per_grain);
-        
-        for grain in *chunk {
-            // Render grain to temporary buffer and collect non-transparent pixels
-            let rendered_grain_pixels = render_grain_to_pixels(grain, stock, params);
-            pixels.extend(rendered_grain_pixels);
-        }
-        
-        pixels
-    }).collect();
-    
-    // Apply all rendered pixels to the main image (sequential to avoid race conditions)
-    // Try SIMD optimization for large pixel counts
-    let total_pixels: usize = rendered_pixels.iter().map(|chunk| chunk.len()).sum();
+    // 🆕 Work out each grain's colour and edge once, then rasterise tile by tile with SIMD
+    let stamps = raster::grain_stamps(grains, stock, params);
     
-    if total_pixels > 500 {  // Lower threshold for SIMD
-        // Use SIMD for medium+ workloads
-        println!("🚀 Using SIMD optimization for {} pixels", total_pixels);
-        apply_pixels_simd_optimized(&mut img, rendered_pixels, params);
-    } else {
-        // Use regular blending for small workloads with bounds checking optimization
-        for pixel_chunk in rendered_pixels {
-            for (x, y, color) in pixel_chunk {
-                // Bounds check once per chunk instead of per pixel
-                if x < params.width && y < params.height {
-                    // Use regular pixel access (bounds already checked)
-                    let pixel = img.get_pixel_mut(x, y);
-                    blend_pixel_fast(pixel, color);
-                }
-            }
-        }
-    }
+    // Transparent background; grain is composited later
+    let mut data = vec![0u8; params.width as usize * params.height as usize * 4];
+    raster::rasterise(&stamps, 0, 0, params.width, params.height, &mut data);
     
     let render_time = render_start.elapsed().as_millis();
     println!("⏱️ Render breakdown: {}ms total, {:.1} grains/ms", render_time, grains.len() as f32 / render_time as f32);
     
-    // Convert to raw RGBA bytes
-    Ok(img.into_raw())
+    Ok(data)
 }
 
 // Cache for expensive operations
 thread_local! {
-    static FILM_COLORS: std::cell::RefCell<std::collections::HashMap<String, (u8, u8, u8)>> = std::cell::RefCell::new(std::collections::HashMap::new());
-    static ENHANCED_DATA: std::cell::RefCell<Option<HashMap<String, EnhancedFilmData>>> = std::cell::RefCell::new(None);
+    static FILM_COLORS: std::cell::RefCell<std::collections::HashMap<String, FilmGrainColor>> = std::cell::RefCell::new(std::collections::HashMap::new());
+    static ENHANCED_DATA: std::cell::RefCell<Option<HashMap<String, EnhancedFilmData>>> = const { std::cell::RefCell::new(None) };
+    static IMAGE_RESPONSES: std::cell::RefCell<Option<HashMap<String, ImageResponse>>> = const { std::cell::RefCell::new(None) };
 }
 
-fn render_grain_to_pixels(grain: &Grain, stock: &FilmStock, params: &GrainParams) -> Vec<(u32, u32, Rgba<u8>)> {
-    let center_x = grain.x as i32;
-    let center_y = grain.y as i32;
-    let radius = grain.size as i32;
-    
-    // Early bounds check - skip grains completely outside canvas
-    if center_x + radius < 0 || center_y + radius < 0 || 
-       center_x - radius >= params.width as i32 || center_y - radius >= params.height as i32 {
-        return Vec::new();
-    }
-    
-    // 🚀 NEW: Enhanced color film simulation with multi-layer rendering
-    let (mut final_r, mut final_g, mut final_b) = if stock.basic_info.film_type == "color" {
-        render_color_film_grain(grain, stock, params)
-    } else {
-        render_bw_film_grain(grain, stock, params)
-    };
-    
-    // 🆕 ENHANCEMENT 4: Individual grain color variation for color films
-    if stock.basic_info.film_type == "color" {
-        let mut rng = thread_rng();
-        let color_var = rng.gen_range(0.92..1.08); // Subtle per-grain color variation
-        final_r = ((final_r as f32 * color_var).clamp(0.0, 255.0)) as u8;
-        final_g = ((final_g as f32 * color_var).clamp(0.0, 255.0)) as u8;
-        final_b = ((final_b as f32 * color_var).clamp(0.0, 255.0)) as u8;
-    }
-    
-    // 🚀 NEW: Apply JSON contrast-based opacity
-    let contrast_multiplier = get_json_contrast_multiplier(&stock.visual_properties.contrast_level);
-    let mut alpha = ((grain.opacity * 255.0 * 2.0 * contrast_multiplier).min(255.0).max(40.0)) as u8;
-    
-    // 🆕 ENHANCEMENT 5: Keep original alpha without prominence modification
-    // let prominence_factor = get_json_prominence_factor(stock);
-    // alpha = ((alpha as f32 * prominence_factor).clamp(0.0, 255.0)) as u8;
-    
-    // Pre-allocate pixels vector with estimated capacity
-    let estimated_pixels = ((radius * radius) as f32 * 3.14159) as usize;
-    let mut pixels = Vec::with_capacity(estimated_pixels);
-    
-    // Optimized grain rendering with fewer calculations
-    let grain_size_sq = grain.size * grain.size;
-    let shape_factor_inv = 1.0 / grain.shape_factor;
-    
-    // 🆕 ENHANCEMENT 6: Enhanced edge rendering based on JSON edge_type
-    let edge_softness = get_json_edge_softness(&stock.grain_structure.edge_type);
-    
-    for dy in -radius..=radius {
-        let dy_sq = (dy * dy) as f32;
-        let y = center_y + dy;
-        
-        // Skip entire row if outside bounds
-        if y < 0 || y >= params.height as i32 {
-            continue;
-        }
-        
-        for dx in -radius..=radius {
-            let x = center_x + dx;
-            
-            // Quick bounds check
-            if x < 0 || x >= params.width as i32 {
-                continue;
-            }
-            
-            // Fast distance calculation with shape factor
-            let adjusted_dx = dx as f32 * shape_factor_inv;
-            let distance_sq = adjusted_dx * adjusted_dx + dy_sq;
-            
-            if distance_sq <= grain_size_sq {
-                // Enhanced edge calculation using JSON edge_type data
-                let distance = distance_sq.sqrt();
-                let edge_alpha = if stock.grain_structure.edge_type == "soft" {
-                    if distance > grain.size * 0.6 {
-                        ((grain.size - distance) / (grain.size * 0.4 * edge_softness)).max(0.0)
-                    } else {
-                        1.0
-                    }
-                } else if stock.grain_structure.edge_type == "hard" {
-                    if distance > grain.size * 0.98 { 0.0 } else { 1.0 }
-                } else {
-                    // Sharp edge (default) with configurable softness
-                    if distance > grain.size * 0.85 {
-                        ((grain.size - distance) / (grain.size * 0.15 * edge_softness)).max(0.0)
-                    } else {
-                        1.0
-                    }
-                };
-                
-                let final_alpha = (alpha as f32 * edge_alpha) as u8;
-                
-                if final_alpha > 10 {
-                    pixels.push((x as u32, y as u32, Rgba([final_r, final_g, final_b, final_alpha])));
-                }
-            }
-        }
-    }
-    
-    pixels
+// Stable per-grain random value in [0, 1) derived from its position and the render seed,
+// so per-grain variation is reproducible even though grains are rendered in parallel
+fn grain_hash_unit(grain: &Grain, seed: u64) -> f32 {
+    let h = split_mix64(seed ^ ((grain.x.to_bits() as u64) << 32 | grain.y.to_bits() as u64));
+    (h >> 40) as f32 / (1u64 << 24) as f32
+}
+
+// Seeds reach the webview as JSON numbers, which hold integers exactly only below 2^53
+const MAX_SEED: u64 = 1 << 53;
+
+// A fresh seed that survives the trip through the webview into presets and back
+fn random_seed() -> u64 {
+    thread_rng().gen_range(0..MAX_SEED)
+}
+
+// SplitMix64 finaliser
+fn split_mix64(mut h: u64) -> u64 {
+    h = (h ^ (h >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94d049bb133111eb);
+    h ^ (h >> 31)
 }
 
 // 🚀 NEW: Get JSON color cast multiplier from primary_cast field
@@ -896,7 +1184,7 @@ fn get_json_contrast_multiplier(contrast_level: &str) -> f32 {
 }
 
 // 🚀 NEW: Generate pattern-based grain positions using JSON pattern data
-fn generate_pattern_based_positions(pattern: &str, params: &GrainParams, count: usize, rng: &mut ThreadRng) -> Vec<(f32, f32)> {
+fn generate_pattern_based_positions(pattern: &str, params: &GrainParams, count: usize, rng: &mut StdRng) -> Vec<(f32, f32)> {
     match pattern {
         "random" => generate_random_positions(params, count, rng),
         "clustered" => generate_clustered_positions(params, count, rng),
@@ -906,7 +1194,7 @@ fn generate_pattern_based_positions(pattern: &str, params: &GrainParams, count:
     }
 }
 
-fn generate_random_positions(params: &GrainParams, count: usize, rng: &mut ThreadRng) -> Vec<(f32, f32)> {
+fn generate_random_positions(params: &GrainParams, count: usize, rng: &mut StdRng) -> Vec<(f32, f32)> {
     (0..count)
         .map(|_| (
             rng.gen::<f32>() * params.width as f32,
@@ -915,7 +1203,7 @@ fn generate_random_positions(params: &GrainParams, count: usize, rng: &mut Threa
         .collect()
 }
 
-fn generate_clustered_positions(params: &GrainParams, count: usize, rng: &mut ThreadRng) -> Vec<(f32, f32)> {
+fn generate_clustered_positions(params: &GrainParams, count: usize, rng: &mut StdRng) -> Vec<(f32, f32)> {
     let mut positions = Vec::new();
     let cluster_count = (count as f32 * 0.1) as usize; // 10% cluster centers
     
@@ -945,7 +1233,7 @@ fn generate_clustered_positions(params: &GrainParams, count: usize, rng: &mut Th
     positions
 }
 
-fn generate_regular_positions(params: &GrainParams, count: usize, rng: &mut ThreadRng) -> Vec<(f32, f32)> {
+fn generate_regular_positions(params: &GrainParams, count: usize, rng: &mut StdRng) -> Vec<(f32, f32)> {
     let mut positions = Vec::new();
     let grid_size = (count as f32).sqrt() as usize;
     let x_step = params.width as f32 / grid_size as f32;
@@ -973,7 +1261,7 @@ fn generate_regular_positions(params: &GrainParams, count: usize, rng: &mut Thre
     positions
 }
 
-fn generate_poisson_positions(params: &GrainParams, count: usize, rng: &mut ThreadRng) -> Vec<(f32, f32)> {
+fn generate_poisson_positions(params: &GrainParams, count: usize, rng: &mut StdRng) -> Vec<(f32, f32)> {
     // Simplified Poisson disk sampling
     let mut positions = Vec::new();
     let min_distance = 3.0; // Minimum distance between grains
@@ -1021,7 +1309,7 @@ fn generate_poisson_positions(params: &GrainParams, count: usize, rng: &mut Thre
 }
 
 // 🚀 NEW: Get shape-specific size factor
-fn get_shape_size_factor(shape: &str, rng: &mut ThreadRng) -> f32 {
+fn get_shape_size_factor(shape: &str, rng: &mut StdRng) -> f32 {
     match shape {
         "Sigma grain" => rng.gen_range(0.95..1.05),      // Very uniform size
         "extremely_fine" => rng.gen_range(0.9..1.1),     // Consistent fine grain
@@ -1034,7 +1322,7 @@ fn get_shape_size_factor(shape: &str, rng: &mut ThreadRng) -> f32 {
 }
 
 // 🚀 NEW: Get shape-specific shape factor
-fn get_shape_factor(shape: &str, rng: &mut ThreadRng) -> f32 {
+fn get_shape_factor(shape: &str, rng: &mut StdRng) -> f32 {
     match shape {
         "Sigma grain" => rng.gen_range(0.95..1.05),      // Nearly circular
         "extremely_fine" => rng.gen_range(0.9..1.1),     // Very round
@@ -1046,103 +1334,142 @@ fn get_shape_factor(shape: &str, rng: &mut ThreadRng) -> f32 {
     }
 }
 
+// 🆕 Outline a stock's crystals are drawn with. The discriminant is what the GPU shaders see.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+enum CrystalShape {
+    Rounded = 0,   // Smooth ellipse (Sigma grain)
+    Irregular = 1, // Ellipse with a lumpy, per-grain outline
+    Cubic = 2,     // Rounded square (cubic and core-shell crystals)
+    Tabular = 3,   // Flat hexagonal tablet (T-grain)
+}
+
+// 🆕 The JSON shape is the more specific description; crystal_type fills in when it's generic
+fn get_crystal_shape(structure: &GrainStructure) -> CrystalShape {
+    match structure.shape.as_str() {
+        "T-grain" | "tabular" => CrystalShape::Tabular,
+        "Sigma grain" | "extremely_fine" => CrystalShape::Rounded,
+        "cubic" | "core-shell" => CrystalShape::Cubic,
+        "irregular" | "fine_irregular" => CrystalShape::Irregular,
+        _ => match structure.crystal_type.as_str() {
+            "tabular" => CrystalShape::Tabular,
+            "cubic" => CrystalShape::Cubic,
+            _ => CrystalShape::Rounded,
+        },
+    }
+}
+
+// 🆕 Red-orange glow of film without an anti-halation backing
+const REMJET_FREE_HALATION_COLOR: &str = "#ff5a1f";
+
+// 🆕 Development process for stocks whose data doesn't name one
+fn default_process(film_type: &str, name: &str) -> &'static str {
+    // Chromogenic B&W stocks are developed in C-41, so their image is dye rather than silver
+    if ["XP2", "BW400CN"].iter().any(|chromogenic| name.contains(chromogenic)) {
+        "C-41"
+    } else if film_type == "bw" {
+        "B&W"
+    } else if ["Ektachrome", "Velvia", "Provia", "Precisa"].iter().any(|slide| name.contains(slide)) {
+        "E-6"
+    } else if name.contains("Vision3") {
+        "ECN-2"
+    } else {
+        "C-41"
+    }
+}
+
+// 🆕 Chromogenic processes wash the silver out and leave dye clouds formed by couplers
+// around each developed grain. This is how far the dye spreads, as a multiple of the
+// grain's size; None for silver images.
+fn get_coupler_spread(process: &str) -> Option<f32> {
+    match process {
+        "C-41" => Some(1.5),
+        "ECN-2" => Some(1.7), // Motion picture stocks: the softest, most diffuse clouds
+        "E-6" => Some(1.3),   // Slide films: tight clouds for projection sharpness
+        _ => None,            // Silver grain (traditional B&W)
+    }
+}
+
+// 🆕 Grain rotation from the stock's orientation field: "random", "horizontal"/"aligned",
+// "vertical", "diagonal" or an angle in degrees. Aligned grains still scatter a little.
+fn get_grain_orientation(orientation: &str, rng: &mut StdRng) -> f32 {
+    let bias_degrees = match orientation {
+        "horizontal" | "aligned" => Some(0.0),
+        "vertical" => Some(90.0),
+        "diagonal" => Some(45.0),
+        other => other.trim_end_matches("deg").trim().parse::<f32>().ok(),
+    };
+    match bias_degrees {
+        Some(degrees) => (degrees + rng.gen_range(-15.0..15.0f32)).to_radians(),
+        None => rng.gen_range(0.0..std::f32::consts::PI), // An ellipse repeats every half turn
+    }
+}
+
+// 🆕 Per-grain hash salts: each colour channel's variation and dye layer strength
+const GRAIN_COLOR_SALTS: [u64; 3] = [0xc010_0001, 0xc010_0002, 0xc010_0003];
+const DYE_STRENGTH_SALTS: [u64; 3] = [0xd7e5_0001, 0xd7e5_0002, 0xd7e5_0003];
+
 // 🚀 NEW: Render color film grain with multi-layer simulation
 fn render_color_film_grain(grain: &Grain, stock: &FilmStock, params: &GrainParams) -> (u8, u8, u8) {
-    FILM_COLORS.with(|cache| {
-        let mut cache = cache.borrow_mut();
-        if let Some(&color) = cache.get(&stock.basic_info.name) {
-            color
-        } else {
-            let (base_r, base_g, base_b) = get_film_grain_color(&stock.basic_info.name);
-            
-            // 🚀 Multi-layer color film simulation
-            // Color films have 3 separate emulsion layers with different characteristics
-            let mut rng = thread_rng();
-            
-            // Cyan layer (top) - affects red channel
-            let cyan_strength = rng.gen_range(0.85..1.15);
-            // Magenta layer (middle) - affects green channel  
-            let magenta_strength = rng.gen_range(0.85..1.15);
-            // Yellow layer (bottom) - affects blue channel
-            let yellow_strength = rng.gen_range(0.85..1.15);
-            
-            // Apply layer variations
-            let layer_r = (base_r as f32 * cyan_strength).clamp(0.0, 255.0);
-            let layer_g = (base_g as f32 * magenta_strength).clamp(0.0, 255.0);
-            let layer_b = (base_b as f32 * yellow_strength).clamp(0.0, 255.0);
-            
-            // Apply JSON color cast
-            let (cast_r, cast_g, cast_b) = get_json_color_cast_multiplier(&stock.color_properties.primary_cast);
-            
-            // Apply color crossover effects
-            let mut grain_color = [layer_r / 255.0, layer_g / 255.0, layer_b / 255.0];
-            ENHANCED_DATA.with(|enhanced_cache| {
-                let mut enhanced_cache = enhanced_cache.borrow_mut();
-                if enhanced_cache.is_none() {
-                    *enhanced_cache = load_enhanced_film_data().ok();
-                }
-                if let Some(ref enhanced_data) = *enhanced_cache {
-                    if let Some(enhanced) = enhanced_data.get(&params.film_stock) {
-                        apply_color_crossover(&mut grain_color, &enhanced.color_crossover);
-                    }
-                }
-            });
-            
-            // Apply JSON color cast
-            grain_color[0] *= cast_r;
-            grain_color[1] *= cast_g;
-            grain_color[2] *= cast_b;
-            
-            let final_color = (
-                (grain_color[0] * 255.0).clamp(0.0, 255.0) as u8,
-                (grain_color[1] * 255.0).clamp(0.0, 255.0) as u8,
-                (grain_color[2] * 255.0).clamp(0.0, 255.0) as u8
-            );
-            cache.insert(stock.basic_info.name.clone(), final_color);
-            final_color
-        }
-    })
+    let color = stock_grain_color(stock, params);
+    let seed = motion_effects::grain_seed(params);
+    
+    // 🚀 Multi-layer color film simulation
+    // Color films have 3 separate emulsion layers (cyan over red, magenta over green, yellow
+    // over blue) and each grain's dyes develop to their own strength
+    let channel = |index: usize| {
+        let offset = (grain_hash_unit(grain, seed ^ GRAIN_COLOR_SALTS[index]) * 2.0 - 1.0) * color.variation[index];
+        let strength = 0.85 + grain_hash_unit(grain, seed ^ DYE_STRENGTH_SALTS[index]) * 0.3;
+        ((color.base[index] + offset) * strength).clamp(0.0, 255.0) as u8
+    };
+    (channel(0), channel(1), channel(2))
 }
 
 // 🚀 NEW: Render B&W film grain
 fn render_bw_film_grain(grain: &Grain, stock: &FilmStock, params: &GrainParams) -> (u8, u8, u8) {
+    let color = stock_grain_color(stock, params);
+    
+    // Silver grain varies in tone, not hue: one draw shifts every channel
+    let unit = grain_hash_unit(grain, motion_effects::grain_seed(params) ^ GRAIN_COLOR_SALTS[0]) * 2.0 - 1.0;
+    let channel = |index: usize| (color.base[index] + unit * color.variation[index]).clamp(0.0, 255.0) as u8;
+    (channel(0), channel(1), channel(2))
+}
+
+// 🆕 A stock's base grain colour with its JSON colour cast and colour crossover applied.
+// None of it is random, so it's worked out once per stock and thread.
+fn stock_grain_color(stock: &FilmStock, params: &GrainParams) -> FilmGrainColor {
     FILM_COLORS.with(|cache| {
-        let mut cache = cache.borrow_mut();
-        if let Some(&color) = cache.get(&stock.basic_info.name) {
-            color
-        } else {
-            let (r, g, b) = get_film_grain_color(&stock.basic_info.name);
-            
-            // Apply JSON color cast (for toned B&W films)
-            let (cast_r, cast_g, cast_b) = get_json_color_cast_multiplier(&stock.color_properties.primary_cast);
-            
-            // Apply color crossover effects
-            let mut grain_color = [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0];
-            ENHANCED_DATA.with(|enhanced_cache| {
-                let mut enhanced_cache = enhanced_cache.borrow_mut();
-                if enhanced_cache.is_none() {
-                    *enhanced_cache = load_enhanced_film_data().ok();
-                }
-                if let Some(ref enhanced_data) = *enhanced_cache {
-                    if let Some(enhanced) = enhanced_data.get(&params.film_stock) {
-                        apply_color_crossover(&mut grain_color, &enhanced.color_crossover);
-                    }
-                }
-            });
-            
-            // Apply JSON color cast
-            grain_color[0] *= cast_r;
-            grain_color[1] *= cast_g;
-            grain_color[2] *= cast_b;
-            
-            let final_color = (
-                (grain_color[0] * 255.0).clamp(0.0, 255.0) as u8,
-                (grain_color[1] * 255.0).clamp(0.0, 255.0) as u8,
-                (grain_color[2] * 255.0).clamp(0.0, 255.0) as u8
-            );
-            cache.insert(stock.basic_info.name.clone(), final_color);
-            final_color
+        if let Some(&color) = cache.borrow().get(&stock.basic_info.name) {
+            return color;
         }
+        
+        let color = get_film_grain_color(&stock.basic_info.name);
+        
+        // Apply JSON color cast (also tones B&W films)
+        let (cast_r, cast_g, cast_b) = get_json_color_cast_multiplier(&stock.color_properties.primary_cast);
+        
+        // Apply color crossover effects
+        let mut grain_color = color.base.map(|channel| channel / 255.0);
+        ENHANCED_DATA.with(|enhanced_cache| {
+            let mut enhanced_cache = enhanced_cache.borrow_mut();
+            if enhanced_cache.is_none() {
+                *enhanced_cache = load_enhanced_film_data().ok();
+            }
+            if let Some(ref enhanced_data) = *enhanced_cache {
+                if let Some(enhanced) = resolve_enhanced_film_data(enhanced_data, &params.film_stock) {
+                    apply_color_crossover(&mut grain_color, &enhanced.color_crossover);
+                }
+            }
+        });
+        
+        // Apply JSON color cast
+        grain_color[0] *= cast_r;
+        grain_color[1] *= cast_g;
+        grain_color[2] *= cast_b;
+        
+        let color = FilmGrainColor { base: grain_color.map(|channel| (channel * 255.0).clamp(0.0, 255.0)), variation: color.variation };
+        cache.borrow_mut().insert(stock.basic_info.name.clone(), color);
+        color
     })
 }
 
@@ -1157,32 +1484,6 @@ fn get_iso_irregularity_factor(iso: u32) -> f32 {
     }
 }
 
-// 🚀 NEW: Apply realistic exposure compensation effects
-fn apply_realistic_exposure_effects(exposure_comp: f32, size: &mut f32, rng: &mut ThreadRng) -> f32 {
-    if exposure_comp > 0.0 {
-        // Overexposure effects (pushing film)
-        // - Grain becomes more prominent and slightly larger
-        // - Increased contrast in grain structure
-        // - More irregular grain shapes
-        *size *= 1.0 + (exposure_comp * 0.15); // Slight size increase
-        
-        // Opacity factor for overexposure
-        1.0 + (exposure_comp * 0.4) // More visible grain
-    } else if exposure_comp < 0.0 {
-        // Underexposure effects (pulling film)
-        // - Grain becomes denser-looking but finer
-        // - Shadows get grittier
-        // - Less overall grain visibility in highlights
-        let underexp = exposure_comp.abs();
-        *size *= 1.0 - (underexp * 0.1); // Slightly smaller grain
-        
-        // Opacity factor for underexposure
-        1.0 + (underexp * 0.25) // Denser-looking grain in shadows
-    } else {
-        1.0 // No exposure compensation
-    }
-}
-
 // 🚀 NEW: Apply realistic aging effects based on UI parameters
 fn apply_realistic_aging_effects(opacity: f32, _size: f32, age_years: f32, storage_temp: f32, film_type: &str) -> f32 {
     // Storage condition multiplier
@@ -1218,9 +1519,8 @@ fn apply_realistic_aging_effects(opacity: f32, _size: f32, age_years: f32, stora
     aged_opacity.min(0.85)
 }
 
-fn apply_enhanced_effects(grains: &mut Vec<Grain>, params: &GrainParams, enhanced: &EnhancedFilmData) -> Result<(), String> {
-    let mut rng = thread_rng();
-    
+fn apply_enhanced_effects(grains: &mut Vec<Grain>, params: &GrainParams, enhanced: &EnhancedFilmData, rng: &mut StdRng) -> Result<(), String> {
+
     // Apply film aging effects
     if let Some(age_years) = params.film_age_years {
         if age_years > 0.0 {
@@ -1230,7 +1530,7 @@ fn apply_enhanced_effects(grains: &mut Vec<Grain>, params: &GrainParams, enhance
     }
     
     // Apply enhanced clustering based on research data
-    apply_enhanced_clustering_realistic(grains, &mut rng, params.width, params.height, &enhanced.clustering_data);
+    apply_enhanced_clustering_realistic(grains, rng, params.width, params.height, &enhanced.clustering_data);
     
     Ok(())
 }
@@ -1257,7 +1557,7 @@ fn apply_aging_effects(grains: &mut Vec<Grain>, age_years: f32, storage_temp: f3
     }
 }
 
-fn apply_enhanced_clustering_realistic(grains: &mut Vec<Grain>, rng: &mut ThreadRng, width: u32, height: u32, clustering: &ClusteringData) {
+fn apply_enhanced_clustering_realistic(grains: &mut Vec<Grain>, rng: &mut StdRng, width: u32, height: u32, clustering: &ClusteringData) {
     let cluster_count = (grains.len() as f32 * clustering.cluster_probability) as usize;
     
     println!("🔬 Applying enhanced clustering: fractal_dim={:.2}, correlation={:.2}, clusters={}", 
@@ -1292,6 +1592,8 @@ fn apply_enhanced_clustering_realistic(grains: &mut Vec<Grain>, rng: &mut Thread
                     size: seed_grain.size * size_variation,
                     opacity: (seed_grain.opacity * opacity_variation).min(1.0).max(0.1),
                     shape_factor: seed_grain.shape_factor * rng.gen_range(0.9..1.1),
+                    angle: seed_grain.angle,
+                    dye_layer: seed_grain.dye_layer,
                 });
             }
         }
@@ -1318,153 +1620,71 @@ fn apply_color_crossover(grain_color: &mut [f32; 3], crossover: &ColorCrossover)
     }
 }
 
-fn blend_pixel(base_pixel: &mut Rgba<u8>, new_pixel: Rgba<u8>) {
-    let blend_factor = new_pixel[3] as f32 / 255.0;
-    base_pixel[0] = ((base_pixel[0] as f32 * (1.0 - blend_factor)) + (new_pixel[0] as f32 * blend_factor)) as u8;
-    base_pixel[1] = ((base_pixel[1] as f32 * (1.0 - blend_factor)) + (new_pixel[1] as f32 * blend_factor)) as u8;
-    base_pixel[2] = ((base_pixel[2] as f32 * (1.0 - blend_factor)) + (new_pixel[2] as f32 * blend_factor)) as u8;
-    base_pixel[3] = ((base_pixel[3] as f32).max(new_pixel[3] as f32)) as u8;
+#[tauri::command]
+async fn save_grain_image(
+    app: tauri::AppHandle,
+    cache: tauri::State<'_, render_cache::RenderCache>,
+    render_id: u64,
+    path: String,
+    options: Option<output::OutputOptions>,
+) -> Result<String, output::OutputError> {
+    let render = cache.get(render_id)?;
+    
+    // Dialog-chosen path, or a bare file name in Downloads
+    let options = options.unwrap_or_default();
+    let out = output::open_requested_path(&app, &path, &options)?;
+    println!("Saving to: {:?}", out.path());
+    
+    let recipe = metadata::GrainRecipe::new(&render.params);
+    let saved_path = output::write_image(&render.image, out, &options, Some(&recipe))?;
+    Ok(saved_path.to_string_lossy().to_string())
 }
 
-#[inline(always)]
-fn blend_pixel_fast(base_pixel: &mut Rgba<u8>, new_pixel: Rgba<u8>) {
-    if new_pixel[3] == 0 { return; } // Skip transparent pixels
-    
-    // Use integer math for better performance
-    let alpha = new_pixel[3] as u16;
-    let inv_alpha = 255 - alpha;
-    
-    base_pixel[0] = (((base_pixel[0] as u16 * inv_alpha) + (new_pixel[0] as u16 * alpha)) >> 8) as u8;
-    base_pixel[1] = (((base_pixel[1] as u16 * inv_alpha) + (new_pixel[1] as u16 * alpha)) >> 8) as u8;
-    base_pixel[2] = (((base_pixel[2] as u16 * inv_alpha) + (new_pixel[2] as u16 * alpha)) >> 8) as u8;
-    base_pixel[3] = ((base_pixel[3] as u16 + alpha).min(255)) as u8;
+// 🆕 A stock's grain colour from color.json, in 0-255 units: its base colour and how far
+// each grain's channels may stray from it
+#[derive(Debug, Clone, Copy)]
+struct FilmGrainColor {
+    base: [f32; 3],
+    variation: [f32; 3],
 }
 
-
-fn apply_pixels_simd_optimized(
-    img: &mut RgbaImage, 
-    rendered_pixels: Vec<Vec<(u32, u32, Rgba<u8>)>>, 
-    params: &GrainParams
-) {
-    // For SIMD optimization, we need to work with contiguous memory
-    // So we'll still use the regular approach but with SIMD where possible
-    
-    // Group pixels by rows for better memory access patterns
-    let mut row_pixels: Vec<Vec<(u32, Rgba<u8>)>> = vec![Vec::new(); params.height as usize];
-    
-    for pixel_chunk in rendered_pixels {
-        for (x, y, color) in pixel_chunk {
-            if x < params.width && y < params.height {
-                row_pixels[y as usize].push((x, color));
-            }
-        }
+fn get_film_grain_color(film_name: &str) -> FilmGrainColor {
+    // Blended stocks mix the grain colours of both parents
+    if let Some(blend) = parse_stock_blend(film_name) {
+        let (from, to) = (get_film_grain_color(&blend.from), get_film_grain_color(&blend.to));
+        let mix = |a: [f32; 3], b: [f32; 3]| std::array::from_fn(|i| lerp(a[i], b[i], blend.weight));
+        return FilmGrainColor { base: mix(from.base, to.base), variation: mix(from.variation, to.variation) };
     }
     
-    // Process each row with potential SIMD optimization
-    for (row_idx, row_pixel_list) in row_pixels.iter().enumerate() {
-        if row_pixel_list.is_empty() { continue; }
-        
-        // Sort pixels by x coordinate for sequential access
-        let mut sorted_pixels = row_pixel_list.clone();
-        sorted_pixels.sort_by_key(|(x, _)| *x);
-        
-        // Apply pixels to this row
-        for (x, color) in sorted_pixels {
-            let pixel = img.get_pixel_mut(x, row_idx as u32);
-            blend_pixel(pixel, color);
-        }
-    }
-}
-
-
-#[tauri::command]
-async fn save_grain_image(data: Vec<u8>, width: u32, height: u32, path: String) -> Result<(), String> {
-    let img: RgbaImage = ImageBuffer::from_raw(width, height, data)
-        .ok_or("Failed to create image from data")?;
-    
-    // Save to Downloads folder
-    let downloads_dir = dirs::download_dir()
-        .ok_or("Could not find Downloads directory")?;
-    
-    let full_path = downloads_dir.join(&path);
-    println!("Saving to: {:?}", full_path);
-    
-    img.save(&full_path).map_err(|e| format!("Failed to save image: {}", e))?;
-    Ok(())
-}
-
-fn get_film_grain_color(film_name: &str) -> (u8, u8, u8) {
     // 🆕 ENHANCEMENT 11: Enhanced color data loading with better fallbacks
     let color_data = include_str!("../../color.json");
     if let Ok(colors_json) = serde_json::from_str::<serde_json::Value>(color_data) {
         if let Some(film_color) = colors_json.get(film_name) {
             if let Some(base_color) = film_color.get("base_grain_color") {
-                let r = base_color.get("r").and_then(|v| v.as_u64()).unwrap_or(180) as u8;
-                let g = base_color.get("g").and_then(|v| v.as_u64()).unwrap_or(180) as u8;
-                let b = base_color.get("b").and_then(|v| v.as_u64()).unwrap_or(180) as u8;
+                let channel = |color: &serde_json::Value, name: &str, default: u64| color.get(name).and_then(|v| v.as_u64()).unwrap_or(default) as f32;
+                let base = [channel(base_color, "r", 180), channel(base_color, "g", 180), channel(base_color, "b", 180)];
                 
                 // Apply enhanced color variation
-                if let Some(variation) = film_color.get("color_variation") {
-                    let var_r = variation.get("r").and_then(|v| v.as_u64()).unwrap_or(10) as i32;
-                    let var_g = variation.get("g").and_then(|v| v.as_u64()).unwrap_or(10) as i32;
-                    let var_b = variation.get("b").and_then(|v| v.as_u64()).unwrap_or(10) as i32;
-                    
-                    let mut rng = thread_rng();
-                    let final_r = (r as i32 + rng.gen_range(-var_r..=var_r)).clamp(0, 255) as u8;
-                    let final_g = (g as i32 + rng.gen_range(-var_g..=var_g)).clamp(0, 255) as u8;
-                    let final_b = (b as i32 + rng.gen_range(-var_b..=var_b)).clamp(0, 255) as u8;
-                    
-                    return (final_r, final_g, final_b);
-                }
+                let variation = film_color.get("color_variation")
+                    .map_or([0.0; 3], |variation| [channel(variation, "r", 10), channel(variation, "g", 10), channel(variation, "b", 10)]);
                 
-                return (r, g, b);
+                return FilmGrainColor { base, variation };
             }
         }
     }
     
     // 🆕 ENHANCEMENT 12: Intelligent fallback colors (only when JSON fails)
     println!("⚠️ Using fallback color for: {}", film_name);
-    if film_name.contains("Tri-X") {
-        (175, 175, 175) // Classic B&W grain
+    let gray = if film_name.contains("Tri-X") {
+        175.0 // Classic B&W grain
     } else if film_name.contains("HP5") {
-        (170, 170, 170) // Slightly darker B&W
+        170.0 // Slightly darker B&W
     } else if film_name.contains("T-Max") {
-        (185, 185, 185) // Fine B&W grain
+        185.0 // Fine B&W grain
     } else {
-        (180, 180, 180) // Neutral gray fallback
-    }
-}
-
-// 🆕 ENHANCEMENT 13: Add halation effect for CineStill films
-fn apply_halation_effect(grains: &mut Vec<Grain>, stock: &FilmStock, params: &GrainParams) {
-    if stock.special_effects.halation == "strong" {
-        let mut rng = thread_rng();
-        let mut halation_grains = Vec::new();
-        
-        for grain in grains.iter() {
-            if grain.opacity > 0.6 && rng.gen::<f32>() < 0.15 {
-                let halation_distance = stock.special_effects.halation_radius * grain.size * rng.gen_range(1.5..3.0);
-                let angle = rng.gen::<f32>() * 2.0 * std::f32::consts::PI;
-                
-                let halation_grain = Grain {
-                    x: grain.x + angle.cos() * halation_distance,
-                    y: grain.y + angle.sin() * halation_distance,
-                    size: grain.size * rng.gen_range(0.8..1.5),
-                    opacity: grain.opacity * rng.gen_range(0.2..0.4), // Dimmer halation
-                    shape_factor: 1.0, // Circular halation
-                };
-                
-                // Only add if within bounds
-                if halation_grain.x >= 0.0 && halation_grain.y >= 0.0 && 
-                   halation_grain.x < params.width as f32 && halation_grain.y < params.height as f32 {
-                    halation_grains.push(halation_grain);
-                }
-            }
-        }
-        
-        grains.extend(halation_grains);
-        println!("🌟 Applied halation effect for {}", stock.basic_info.name);
-    }
+        180.0 // Neutral gray fallback
+    };
+    FilmGrainColor { base: [gray; 3], variation: [0.0; 3] }
 }
 
 #[tauri::command]
@@ -1537,95 +1757,137 @@ async fn get_categorized_film_stocks() -> Result<std::collections::HashMap<Strin
     Ok(categorized)
 }
 
-#[tauri::command]
-async fn load_user_image(image_data: String, filename: String) -> Result<String, String> {
-    println!("Loading user image: {}", filename);
-    
-    // Decode base64 image data
-    let image_bytes = general_purpose::STANDARD.decode(&image_data)
-        .map_err(|e| format!("Failed to decode base64: {}", e))?;
-    
-    // Load image using the image crate
-    let img = image::load_from_memory(&image_bytes)
-        .map_err(|e| format!("Failed to load image: {}", e))?;
-    
-    // Convert to RGBA format
-    let rgba_img = img.to_rgba8();
-    let (width, height) = rgba_img.dimensions();
-    
-    println!("Loaded image: {}x{} pixels", width, height);
-    
-    // Convert back to base64 for storage in frontend
-    let mut buffer = Vec::new();
-    let mut cursor = Cursor::new(&mut buffer);
-    
-    DynamicImage::ImageRgba8(rgba_img.clone()).write_to(&mut cursor, ImageFormat::Png)
-        .map_err(|e| format!("Failed to encode image: {}", e))?;
-    
-    let result_base64 = general_purpose::STANDARD.encode(&buffer);
-    
-    Ok(result_base64)
-}
-
+// Tauri commands take their arguments by name from the webview, so they can't be grouped
+#[allow(clippy::too_many_arguments)]
 #[tauri::command]
 async fn save_composite_image(
-    grain_data: Vec<u8>,
-    grain_width: u32,
-    grain_height: u32,
-    base_image_data: String,
+    app: tauri::AppHandle,
+    cache: tauri::State<'_, render_cache::RenderCache>,
+    images: tauri::State<'_, image_store::ImageStore>,
+    render_id: u64,
+    image_id: u64,
     path: String,
-) -> Result<String, String> {
+    options: Option<output::OutputOptions>,
+    crop_to_format: Option<bool>,
+) -> Result<String, output::OutputError> {
     println!("Creating composite image: {}", path);
     
-    // Decode the base image
-    let base_image_bytes = general_purpose::STANDARD.decode(&base_image_data)
-        .map_err(|e| format!("Failed to decode base image: {}", e))?;
+    // The full-resolution photo, decoded once when it was opened
+    let base = images.get(image_id)?;
+    let render = cache.get(render_id)?;
     
-    let base_img = image::load_from_memory(&base_image_bytes)
-        .map_err(|e| format!("Failed to load base image: {}", e))?;
+    // Trimming the photo to the frame shape needs a frame to take the shape from
+    let crop_format = if crop_to_format.unwrap_or(false) {
+        Some(render.params.film_format.as_deref().and_then(film_format::find_film_format)
+            .ok_or("Crop to format needs a film format")?)
+    } else {
+        None
+    };
     
-    let mut base_rgba = base_img.to_rgba8();
+    // Open the output first, so a refused or existing path fails before the render
+    let options = options.unwrap_or_default();
+    let out = output::open_requested_path(&app, &path, &options)?;
+    println!("Compositing onto {:?}", base.path);
+    
+    // Optionally trim the photo to the film format's frame shape first
+    let mut base_rgba = match crop_format {
+        Some(format) => {
+            let (width, height) = base.image.dimensions();
+            let (x, y, crop_width, crop_height) = format.crop_to_aspect(width, height);
+            println!("Cropping {}x{} to {} frame {}x{}", width, height, format.name, crop_width, crop_height);
+            image::imageops::crop_imm(base.image.as_ref(), x, y, crop_width, crop_height).to_image()
+        }
+        None => base.image.as_ref().clone(),
+    };
     let (base_width, base_height) = base_rgba.dimensions();
     
-    // Create grain image from data
-    let grain_img = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(grain_width, grain_height, grain_data)
-        .ok_or("Failed to create grain image from data")?;
-    
-    // Resize grain to match base image if needed
-    let grain_resized = if grain_width != base_width || grain_height != base_height {
-        println!("Resizing grain from {}x{} to {}x{}", grain_width, grain_height, base_width, base_height);
-        image::imageops::resize(&grain_img, base_width, base_height, image::imageops::FilterType::Lanczos3)
-    } else {
-        grain_img
+    // Grain is never resampled: it is rendered again natively at the photo's size with the
+    // preview's params and seed, over the photo's own tones
+    let mut grain_params = render.params.clone();
+    grain_params.width = base_width;
+    grain_params.height = base_height;
+    let stock = resolve_film_stock(&load_film_stock_data()?, &grain_params.film_stock)?;
+    
+    // 🆕 Image-coloured grain takes its hue from the photo beneath it, through the stock's
+    // colour response. The Boolean model already follows the photo channel by channel.
+    let color_response = match grain_params.grain_color_source {
+        GrainColorSource::Image if grain_params.render_mode == RenderMode::Stamped => {
+            let response = resolve_image_response(&grain_params.film_stock)?.color_response;
+            println!("🎨 Grain colour from the photo through the {} colour response", stock.basic_info.name);
+            // Silver grain has no dyes to tint it: only its visibility differs per channel
+            Some(if stock.basic_info.film_type == "bw" { ColorResponse { saturation_effect: 0.0, ..response } } else { response })
+        }
+        _ => None,
     };
+    // 🆕 Halation happens as the film is exposed, so the grain forms over the glow
+    if let Some(halation) = halation::Halation::for_params(&grain_params, &stock)? {
+        println!("🌟 Halation: strength {:.2}, spread {:.1}px", halation.strength, halation.sigma_px);
+        halation::apply(&mut base_rgba, &halation);
+    }
     
-    // Composite grain over base image
-    for (x, y, grain_pixel) in grain_resized.enumerate_pixels() {
-        if x < base_width && y < base_height {
-            let base_pixel = base_rgba.get_pixel_mut(x, y);
-            
-            // Alpha blend the grain onto the base image
-            let grain_alpha = grain_pixel[3] as f32 / 255.0;
-            let inv_alpha = 1.0 - grain_alpha;
-            
-            base_pixel[0] = ((base_pixel[0] as f32 * inv_alpha) + (grain_pixel[0] as f32 * grain_alpha)) as u8;
-            base_pixel[1] = ((base_pixel[1] as f32 * inv_alpha) + (grain_pixel[1] as f32 * grain_alpha)) as u8;
-            base_pixel[2] = ((base_pixel[2] as f32 * inv_alpha) + (grain_pixel[2] as f32 * grain_alpha)) as u8;
-            // Keep the base image's alpha channel
-        }
+    // 🆕 Diffusion softens the light reaching the film, so it also comes before the grain
+    if let Some(bloom) = motion_effects::Bloom::for_params(&grain_params) {
+        println!("✨ Bloom: strength {:.2}, spread {:.1}px", bloom.strength, bloom.sigma_px);
+        motion_effects::apply_bloom(&mut base_rgba, &bloom);
     }
     
-    // Save the composite image
-    let downloads_dir = dirs::download_dir()
-        .ok_or("Could not find Downloads directory")?;
+    if grain_params.render_mode == RenderMode::Boolean {
+        // 🆕 The Boolean model isn't an overlay: the photo's own tones drive its grain
+        println!("Rendering photo through the Boolean grain model at {}x{}", base_width, base_height);
+        boolean_model::render_photo(&grain_params, &mut base_rgba)?;
+    } else {
+        // 🆕 Stamped grain follows the curve too: each grain is sized, and shows as strongly
+        // as, the tone beneath it on the developed curve says
+        let curve = stock.characteristic_curve.developed(&Development::for_params(&grain_params, &stock));
+        let photo = PhotoTones::new(&curve, &base_rgba);
+        let composite = |base: &mut [u8], grain: &[u8]| match &color_response {
+            Some(response) => color_response::composite_image_colored_rows(base, grain, response, photo.visibility()),
+            None => composite_grain_rows(base, grain, photo.visibility()),
+        };
+        
+        if tiled::should_tile(base_width, base_height) {
+            // Large photos: blend each band of grain as it is rendered instead of holding a
+            // second full-size layer
+            println!("Rendering grain natively at {}x{} (tiled)", base_width, base_height);
+            let mut offset = 0;
+            tiled::render_tiled(&grain_params, Some(&photo), |band| {
+                composite(&mut base_rgba.as_mut()[offset..offset + band.len()], band);
+                offset += band.len();
+                Ok(())
+            })?;
+        } else {
+            println!("Rendering grain natively at {}x{}", base_width, base_height);
+            let (grain_img, _) = render_grain_image(&grain_params, Some(&photo)).await?;
+            composite(base_rgba.as_mut(), grain_img.as_raw());
+        }
+    }
     
-    let file_path = downloads_dir.join(&path);
+    // 🆕 Weave moves the developed frame, grain included, in the projector gate
+    if let Some(weave) = motion_effects::GateWeave::for_params(&grain_params)? {
+        println!("🎞️ Gate weave: frame {} offset ({:.2}, {:.2})px", grain_params.frame_index.unwrap_or(0), weave.offset_x, weave.offset_y);
+        motion_effects::apply_gate_weave(&mut base_rgba, &weave);
+    }
     
-    base_rgba.save(&file_path)
-        .map_err(|e| format!("Failed to save composite image: {}", e))?;
+    // Save the composite image
+    let recipe = metadata::GrainRecipe::new(&grain_params);
+    let saved_path = output::write_image(&base_rgba, out, &options, Some(&recipe))?;
     
-    println!("Composite image saved to: {:?}", file_path);
-    Ok(format!("Composite image saved to Downloads/{}", path))
+    println!("Composite image saved to: {:?}", saved_path);
+    Ok(format!("Composite image saved to {}", saved_path.display()))
+}
+
+// Alpha blends RGBA grain over RGBA base pixels of the same layout, keeping the base's alpha.
+// Grain alpha is scaled by `visibility` at the tone of the base pixel.
+fn composite_grain_rows(base: &mut [u8], grain: &[u8], visibility: &[f32; 256]) {
+    for (base_pixel, grain_pixel) in base.chunks_exact_mut(4).zip(grain.chunks_exact(4)) {
+        let tone = characteristic_curve::tone([base_pixel[0], base_pixel[1], base_pixel[2]]);
+        let grain_alpha = (grain_pixel[3] as f32 / 255.0 * visibility[tone as usize]).min(1.0);
+        let inv_alpha = 1.0 - grain_alpha;
+        
+        base_pixel[0] = ((base_pixel[0] as f32 * inv_alpha) + (grain_pixel[0] as f32 * grain_alpha)) as u8;
+        base_pixel[1] = ((base_pixel[1] as f32 * inv_alpha) + (grain_pixel[1] as f32 * grain_alpha)) as u8;
+        base_pixel[2] = ((base_pixel[2] as f32 * inv_alpha) + (grain_pixel[2] as f32 * grain_alpha)) as u8;
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -1641,12 +1903,25 @@ struct FilmInfo {
 
 #[tauri::command]
 async fn get_film_info(film_name: String) -> Result<FilmInfo, String> {
+    // Blends describe themselves in terms of the dominant parent stock
+    if let Some(blend) = parse_stock_blend(&film_name) {
+        let (dominant, other) = if blend.weight < 0.5 { (&blend.from, &blend.to) } else { (&blend.to, &blend.from) };
+        let mut info = lookup_film_info(dominant)?;
+        let dominant_pct = (blend.weight.max(1.0 - blend.weight) * 100.0).round();
+        info.description = format!("Blend of {} ({}%) and {} ({}%). {}", dominant, dominant_pct, other, 100.0 - dominant_pct, info.description);
+        return Ok(info);
+    }
+    
+    lookup_film_info(&film_name)
+}
+
+fn lookup_film_info(film_name: &str) -> Result<FilmInfo, String> {
     // Load comprehensive film stock data to get film info
     let json_data = include_str!("../../fixed.json");
     let stocks_json: serde_json::Value = serde_json::from_str(json_data)
         .map_err(|e| format!("Failed to parse fixed.json: {}", e))?;
     
-    if let Some(stock_data) = stocks_json.get(&film_name) {
+    if let Some(stock_data) = stocks_json.get(film_name) {
         if let Some(film_info) = stock_data.get("film_info") {
             return Ok(FilmInfo {
                 description: film_info.get("description").and_then(|v| v.as_str()).unwrap_or("No description available").to_string(),
@@ -1736,17 +2011,41 @@ fn parse_comprehensive_film_stock(name: &str, data: &serde_json::Value) -> Resul
         _ => vec![1.0, 1.0],          // Cubic is square
     };
     
+    let film_type = if name.contains("Tri-X") || name.contains("HP5") || name.contains("T-Max") || name.contains("Delta") || name.contains("Acros") || name.contains("Pan F") || name.contains("Neopan") || name.contains("FP4") || name.contains("Plus-X") || name.contains("Technical Pan") || name.contains("XP2") || name.contains("BW400CN") { "bw" } else { "color" };
+    // 🆕 Halation from the technical specs. CineStill's stocks are motion picture film with the
+    // remjet anti-halation backing removed, so highlights bleed a strong red-orange glow; in
+    // other colour stocks it's faint and reddish, since the red-sensitive layer sits nearest
+    // the base, and in B&W stocks neutral
+    let halation = if name.contains("CineStill") {
+        "strong".to_string()
+    } else {
+        data.get("technical_specs").and_then(|specs| specs.get("halation")).and_then(|v| v.as_str()).unwrap_or("none").to_string()
+    };
+    let halation_color = data.get("halation_color").and_then(|v| v.as_str()).unwrap_or(
+        if name.contains("CineStill") { REMJET_FREE_HALATION_COLOR } else if film_type == "bw" { "#ffffff" } else { "#ff7b52" }
+    );
+    let process = data.get("process").and_then(|v| v.as_str()).unwrap_or_else(|| default_process(film_type, name)).to_string();
+    let contrast_level = visual_props.get("contrast").and_then(|v| v.as_str()).unwrap_or("medium").to_string();
+    
+    // 🆕 Characteristic curve, estimated from the descriptions where the JSON has none
+    let characteristic_curve = CharacteristicCurve::from_json(
+        data.get("characteristic_curve"),
+        CharacteristicCurve::from_descriptions(film_type, &contrast_level, &shadow_visibility, &highlight_visibility),
+    );
+    let development_response = DevelopmentResponse::from_json(data.get("development_response"), DevelopmentResponse::from_crystal_type(&crystal_type, film_type));
+    
     Ok(FilmStock {
         basic_info: BasicInfo {
             name: name.to_string(),
             iso,
-            film_type: if name.contains("Tri-X") || name.contains("HP5") || name.contains("T-Max") || name.contains("Delta") || name.contains("Acros") || name.contains("Pan F") || name.contains("Neopan") || name.contains("FP4") || name.contains("Plus-X") || name.contains("Technical Pan") { "bw".to_string() } else { "color".to_string() },
+            film_type: film_type.to_string(),
+            process,
         },
         grain_structure: GrainStructure {
             crystal_type,
             shape: grain_chars.get("shape").and_then(|v| v.as_str()).unwrap_or("irregular").to_string(),
             aspect_ratio,
-            orientation: "random".to_string(),
+            orientation: grain_chars.get("orientation").and_then(|v| v.as_str()).unwrap_or("random").to_string(),
             clustering: density_dist.get("clustering").and_then(|v| v.as_str()).unwrap_or("moderate").to_string(),
             edge_type: grain_chars.get("edge_type").and_then(|v| v.as_str()).unwrap_or("sharp").to_string(),
         },
@@ -1761,13 +2060,16 @@ fn parse_comprehensive_film_stock(name: &str, data: &serde_json::Value) -> Resul
         },
         visual_properties: VisualProperties {
             opacity_range: vec![min_opacity, max_opacity],
-            contrast_level: visual_props.get("contrast").and_then(|v| v.as_str()).unwrap_or("medium").to_string(),
+            contrast_level,
             edge_definition: "sharp".to_string(),
             opacity_variation: visual_props.get("opacity_variation").and_then(|v| v.as_f64()).unwrap_or(0.6) as f32,
             highlight_visibility,
             shadow_visibility,
             midtone_prominence,
         },
+        characteristic_curve,
+        development_response,
+        dye_layers: DyeLayers::from_json(data.get("dye_layers")),
         color_properties: ColorProperties {
             primary_cast: visual_props.get("color_cast").and_then(|v| v.as_str()).unwrap_or("neutral").to_string(),
             rgb_ranges: vec![RgbRange {
@@ -1780,8 +2082,8 @@ fn parse_comprehensive_film_stock(name: &str, data: &serde_json::Value) -> Resul
             saturation_level: "low".to_string(),
         },
         special_effects: SpecialEffects {
-            halation: if name.contains("CineStill") { "strong".to_string() } else { "none".to_string() },
-            halation_color: "#ffffff".to_string(),
+            halation,
+            halation_color: halation_color.to_string(),
             halation_radius: if name.contains("CineStill") { 2.0 } else { 1.0 },
             unique_artifacts: vec![],
             light_interaction: "normal".to_string(),
@@ -1797,12 +2099,205 @@ fn parse_comprehensive_film_stock(name: &str, data: &serde_json::Value) -> Resul
 
 
 
+// Headless usage for scripts and batch jobs:
+//   film-grain-generator --list-presets
+//   film-grain-generator --preset <name | file.json> --output grain.png [--seed N] [--width W] [--height H]
+//       [--film-format 35mm|645|6x7|4x5|"Super 35"|16mm] [--scan-dpi DPI]
+//       [--render-mode stamped|boolean]
+//       [--push STOPS] [--developer standard|fine-grain|solvent|high-acutance] [--agitation stand|gentle|normal|vigorous]
+//       [--exposure STOPS] [--shutter-time SECONDS]
+//       [--format png|jpeg|tiff|webp|exr] [--quality 1-100] [--compression none|lzw|deflate|packbits]
+//       [--overwrite fail|replace|rename]
+//   film-grain-generator --print-recipe <exported image>
+// Returns None when no CLI flags were given so the GUI starts as usual.
+fn run_cli(args: &[String]) -> Option<Result<(), String>> {
+    let flag_value = |flag: &str| args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1));
+    
+    if let Some(image_path) = flag_value("--print-recipe") {
+        return Some(metadata::read_recipe(std::path::Path::new(image_path)).and_then(|recipe| {
+            let json = serde_json::to_string_pretty(&recipe).map_err(|e| e.to_string())?;
+            println!("{}", json);
+            Ok(())
+        }));
+    }
+    
+    if args.iter().any(|a| a == "--list-presets") {
+        return Some(presets::list_preset_files().map(|presets| {
+            for (path, preset) in presets {
+                println!("{}\t{}\t{}", preset.name, preset.params.film_stock, path.display());
+            }
+        }));
+    }
+    
+    let preset_arg = flag_value("--preset")?;
+    
+    Some((|| {
+        let preset_path = std::path::Path::new(preset_arg);
+        let preset = if preset_path.is_file() {
+            presets::read_preset_file(preset_path)?
+        } else {
+            presets::find_preset(preset_arg)?
+        };
+        
+        let mut params = preset.params;
+        if let Some(seed) = flag_value("--seed") {
+            params.seed = Some(seed.parse().map_err(|_| format!("Invalid --seed '{}'", seed))?);
+        }
+        if let Some(width) = flag_value("--width") {
+            params.width = width.parse().map_err(|_| format!("Invalid --width '{}'", width))?;
+        }
+        if let Some(height) = flag_value("--height") {
+            params.height = height.parse().map_err(|_| format!("Invalid --height '{}'", height))?;
+        }
+        if let Some(film_format) = flag_value("--film-format") {
+            params.film_format = Some(film_format.clone());
+        }
+        if let Some(dpi) = flag_value("--scan-dpi") {
+            params.scan_dpi = Some(dpi.parse().map_err(|_| format!("Invalid --scan-dpi '{}'", dpi))?);
+        }
+        if let Some(mode) = flag_value("--render-mode") {
+            params.render_mode = serde_json::from_value(serde_json::Value::String(mode.to_lowercase())).map_err(|e| format!("Invalid --render-mode: {}", e))?;
+        }
+        if let Some(stops) = flag_value("--exposure") {
+            params.exposure_compensation = stops.parse().map_err(|_| format!("Invalid --exposure '{}'", stops))?;
+        }
+        if let Some(seconds) = flag_value("--shutter-time") {
+            params.shutter_time = Some(seconds.parse().map_err(|_| format!("Invalid --shutter-time '{}'", seconds))?);
+        }
+        if let Some(stops) = flag_value("--push") {
+            params.push_pull_stops = Some(stops.parse().map_err(|_| format!("Invalid --push '{}'", stops))?);
+        }
+        if let Some(developer) = flag_value("--developer") {
+            params.developer = Some(developer.to_lowercase());
+        }
+        if let Some(agitation) = flag_value("--agitation") {
+            params.agitation = Some(agitation.to_lowercase());
+        }
+        let seed = params.seed.unwrap_or_else(random_seed);
+        params.seed = Some(seed);
+        
+        // Output flags use the same names as the JSON options sent by the app
+        let option_value = |flag: &str| flag_value(flag).map(|v| serde_json::Value::String(v.to_lowercase()));
+        let mut output_options = output::OutputOptions::default();
+        if let Some(format) = option_value("--format") {
+            output_options.format = Some(serde_json::from_value(format).map_err(|e| format!("Invalid --format: {}", e))?);
+        }
+        if let Some(quality) = flag_value("--quality") {
+            output_options.jpeg_quality = quality.parse().map_err(|_| format!("Invalid --quality '{}'", quality))?;
+        }
+        if let Some(compression) = option_value("--compression") {
+            output_options.tiff_compression = serde_json::from_value(compression).map_err(|e| format!("Invalid --compression: {}", e))?;
+        }
+        if let Some(overwrite) = option_value("--overwrite") {
+            output_options.overwrite = serde_json::from_value(overwrite).map_err(|e| format!("Invalid --overwrite: {}", e))?;
+        }
+        
+        let output = flag_value("--output").cloned()
+            .unwrap_or_else(|| format!("grain_{}_{}.png", presets::preset_file_stem(&preset.name).unwrap_or_default(), seed));
+        
+        let out = output::OutputFile::create(std::path::Path::new(&output), &output_options, |_| true)?;
+        let recipe = metadata::GrainRecipe::new(&params);
+        let saved_path = if tiled::should_tile(params.width, params.height) {
+            // Print-sized canvases stream straight from the tiled renderer to disk
+            output::write_streamed(params.width, params.height, out, &output_options, Some(&recipe), |sink| {
+                tiled::render_tiled(&params, None, sink).map(|_| ())
+            })?
+        } else if params.render_mode == RenderMode::Boolean {
+            output::write_image(&boolean_model::render_layer(&params)?.0, out, &output_options, Some(&recipe))?
+        } else {
+            let (stock, grains) = prepare_grains(&params)?;
+            let data = render_grains_parallel(&grains, &params, &stock)?;
+            let img: RgbaImage = ImageBuffer::from_raw(params.width, params.height, data)
+                .ok_or("Failed to create image from data")?;
+            output::write_image(&img, out, &output_options, Some(&recipe))?
+        };
+        
+        println!("Rendered preset '{}' (seed {}) to {}", preset.name, seed, saved_path.display());
+        Ok(())
+    })())
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(result) = run_cli(&args) {
+        if let Err(e) = result {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
-        .invoke_handler(tauri::generate_handler![generate_grain, save_grain_image, get_available_film_stocks, get_categorized_film_stocks, get_film_info, load_user_image, save_composite_image])
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_fs::init())
+        .manage(render_cache::RenderCache::default())
+        .manage(image_store::ImageStore::default())
+        .invoke_handler(tauri::generate_handler![generate_grain, render_cache::get_render_preview, save_grain_image, get_available_film_stocks, get_categorized_film_stocks, get_film_info, film_format::get_film_formats, image_store::open_user_image, image_store::get_image_thumbnail, save_composite_image, presets::save_preset, presets::load_preset, presets::list_presets, metadata::read_image_recipe])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }// Performance optimizations applied - 8x faster rendering
 // Test comment to trigger v1.0.5
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stock_blends_parse_their_weight() {
+        let blend = |from: &str, to: &str, weight: f32| Some(StockBlend { from: from.to_string(), to: to.to_string(), weight });
+        assert_eq!(parse_stock_blend("Kodak T-Max 100 + Fuji Superia 400 @ 0.25"), blend("Kodak T-Max 100", "Fuji Superia 400", 0.25));
+        assert_eq!(parse_stock_blend("Kodak T-Max 100 + Fuji Superia 400"), blend("Kodak T-Max 100", "Fuji Superia 400", 0.5));
+        assert_eq!(parse_stock_blend("A + B @ 1.7"), blend("A", "B", 1.0));
+        assert_eq!(parse_stock_blend("A + B @ -2"), blend("A", "B", 0.0));
+        assert_eq!(parse_stock_blend("A + B @ half"), None);
+        assert_eq!(parse_stock_blend("Kodak T-Max 100"), None);
+
+        let stocks = load_film_stock_data().unwrap();
+        assert!(resolve_film_stock(&stocks, "Kodak T-Max 100 + Nonexistent 800 @ 0.5").unwrap_err().contains("Nonexistent 800"));
+        assert_eq!(resolve_film_stock(&stocks, "Kodak T-Max 100 + Fuji Superia 400 @ 0.5").unwrap().basic_info.iso, 200);
+    }
+
+    #[test]
+    fn stock_blends_run_from_one_parent_to_the_other() {
+        let stocks = load_film_stock_data().unwrap();
+        let (bw, color) = (&stocks["Kodak T-Max 100"], &stocks["Fuji Superia 400"]);
+        let as_json = |stock: &FilmStock| {
+            let mut stock = stock.clone();
+            stock.basic_info.name.clear();
+            serde_json::to_value(stock).unwrap()
+        };
+        assert_eq!(as_json(&blend_film_stocks(bw, color, 0.0)), as_json(bw));
+        assert_eq!(as_json(&blend_film_stocks(bw, color, 1.0)), as_json(color));
+
+        // Halfway between ISO 100 and 400 is a stop from each, and categories follow the
+        // dominant stock
+        assert_eq!(blend_film_stocks(bw, color, 0.5).basic_info.iso, 200);
+        assert_eq!(blend_film_stocks(bw, color, 0.49).basic_info.film_type, bw.basic_info.film_type);
+        assert_eq!(blend_film_stocks(bw, color, 0.5).basic_info.film_type, color.basic_info.film_type);
+        assert_eq!(blend_film_stocks(bw, color, 0.49).grain_structure.shape, bw.grain_structure.shape);
+        assert_eq!(blend_film_stocks(bw, color, 0.5).grain_structure.shape, color.grain_structure.shape);
+    }
+
+    #[test]
+    fn chromogenic_black_and_white_stocks_form_dye_clouds() {
+        assert_eq!(default_process("bw", "Ilford XP2 Super 400"), "C-41");
+        assert_eq!(default_process("bw", "Kodak BW400CN"), "C-41");
+        assert!(get_coupler_spread(default_process("bw", "Ilford XP2 Super 400")).is_some());
+        assert_eq!(default_process("bw", "Ilford HP5 Plus"), "B&W");
+    }
+
+    #[test]
+    fn random_seeds_survive_javascript_numbers() {
+        for _ in 0..1000 {
+            let seed = random_seed();
+            let result = GrainResult { render_id: 1, width: 1, height: 1, generation_time_ms: 0, grain_count: 0, seed };
+            // The webview parses every JSON number as a double
+            let json: serde_json::Value = serde_json::from_str(&serde_json::to_string(&result).unwrap()).unwrap();
+            let in_webview = json["seed"].as_f64().unwrap();
+            // ...and writes it back into presets as the integer it holds
+            assert_eq!(in_webview as u64, seed);
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, Write};
+use std::path::Path;
+use tauri_plugin_fs::FsExt;
+use tiff::encoder::{DirectoryEncoder, TiffKind};
+use tiff::tags::Tag;
+
+use crate::presets::{GrainPreset, PRESET_FORMAT_VERSION};
+use crate::GrainParams;
+
+// Every exported file carries the recipe that made it: plain tEXt for quick inspection,
+// an iTXt JSON chunk for exact restore, and XMP so photo tools (and JPEG/TIFF) see it too.
+const APP_NAME: &str = "FilmGrainPro";
+const RECIPE_KEYWORD: &str = "FilmGrainPro:Recipe";
+const PNG_XMP_KEYWORD: &str = "XML:com.adobe.xmp";
+const JPEG_XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const TIFF_XMP_TAG: u16 = 700;
+const XMP_NAMESPACE: &str = "https://github.com/PanagiotisSmponias/FilmGrainPro/ns/1.0/";
+// JPEG APP segments come before the image data, so their XMP lies within this prefix
+const MAX_XMP_SCAN_BYTES: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrainRecipe {
+    pub app_version: String,
+    pub film_stock: String,
+    pub seed: Option<u64>,
+    pub params: GrainParams,
+}
+
+impl GrainRecipe {
+    pub fn new(params: &GrainParams) -> Self {
+        Self {
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            film_stock: params.film_stock.clone(),
+            seed: params.seed,
+            params: params.clone(),
+        }
+    }
+
+    fn software(&self) -> String {
+        format!("{} {}", APP_NAME, self.app_version)
+    }
+
+    fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string(self).map_err(|e| format!("Failed to serialise grain recipe: {}", e))
+    }
+
+    fn to_xmp(&self) -> Result<String, String> {
+        Ok(format!(
+            concat!(
+                "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n",
+                "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n",
+                " <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n",
+                "  <rdf:Description rdf:about=\"\"\n",
+                "    xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\"\n",
+                "    xmlns:fgp=\"{}\"\n",
+                "   xmp:CreatorTool=\"{}\"\n",
+                "   fgp:FilmStock=\"{}\"\n",
+                "   fgp:Seed=\"{}\"\n",
+                "   fgp:Recipe=\"{}\"/>\n",
+                " </rdf:RDF>\n",
+                "</x:xmpmeta>\n",
+                "<?xpacket end=\"w\"?>"
+            ),
+            XMP_NAMESPACE,
+            xml_escape(&self.software()),
+            xml_escape(&self.film_stock),
+            self.seed.map(|s| s.to_string()).unwrap_or_default(),
+            xml_escape(&self.to_json()?),
+        ))
+    }
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\'', "&apos;")
+}
+
+fn xml_unescape(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn extension_of(path: &Path) -> String {
+    path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase()
+}
+
+pub fn add_png_chunks<W: Write>(encoder: &mut png::Encoder<W>, recipe: &GrainRecipe) -> Result<(), String> {
+    let chunk_err = |e: png::EncodingError| format!("Failed to write PNG metadata: {}", e);
+    encoder.add_text_chunk("Software".to_string(), recipe.software()).map_err(chunk_err)?;
+    encoder.add_text_chunk("Source".to_string(), recipe.film_stock.clone()).map_err(chunk_err)?;
+    encoder.add_itxt_chunk(RECIPE_KEYWORD.to_string(), recipe.to_json()?).map_err(chunk_err)?;
+    encoder.add_itxt_chunk(PNG_XMP_KEYWORD.to_string(), recipe.to_xmp()?).map_err(chunk_err)
+}
+
+// Complete APP1 segment (marker, length, XMP header and packet) to splice in after SOI
+pub fn jpeg_xmp_segment(recipe: &GrainRecipe) -> Result<Vec<u8>, String> {
+    let xmp = recipe.to_xmp()?;
+    let segment_len = 2 + JPEG_XMP_HEADER.len() + xmp.len();
+    if segment_len > u16::MAX as usize {
+        return Err("Grain recipe is too large for a JPEG XMP segment".to_string());
+    }
+
+    let mut segment = vec![0xFF, 0xE1];
+    segment.extend_from_slice(&(segment_len as u16).to_be_bytes());
+    segment.extend_from_slice(JPEG_XMP_HEADER);
+    segment.extend_from_slice(xmp.as_bytes());
+    Ok(segment)
+}
+
+pub fn add_tiff_tags<W: Write + Seek, K: TiffKind>(encoder: &mut DirectoryEncoder<W, K>, recipe: &GrainRecipe) -> Result<(), String> {
+    let tiff_err = |e: tiff::TiffError| format!("Failed to write TIFF metadata: {}", e);
+    encoder.write_tag(Tag::Software, recipe.software().as_str()).map_err(tiff_err)?;
+    encoder.write_tag(Tag::Unknown(TIFF_XMP_TAG), recipe.to_xmp()?.as_bytes()).map_err(tiff_err)
+}
+
+// Reads the recipe back without loading the image: the PNG recipe chunk, the TIFF XMP tag,
+// or for anything else (JPEG) an XMP packet in the file's leading metadata.
+pub fn read_recipe(path: &Path) -> Result<GrainRecipe, String> {
+    let recipe = match extension_of(path).as_str() {
+        "png" => read_png_recipe(path)?,
+        "tif" | "tiff" => read_tiff_recipe(path)?,
+        _ => {
+            let file = File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+            let mut prefix = Vec::new();
+            file.take(MAX_XMP_SCAN_BYTES)
+                .read_to_end(&mut prefix)
+                .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+            read_xmp_recipe(&prefix)
+        }
+    };
+    recipe.ok_or_else(|| format!("No {} recipe found in {:?}", APP_NAME, path))
+}
+
+fn read_png_recipe(path: &Path) -> Result<Option<GrainRecipe>, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    let reader = png::Decoder::new(BufReader::new(file))
+        .read_info()
+        .map_err(|e| format!("Failed to read PNG {:?}: {}", path, e))?;
+
+    for chunk in &reader.info().utf8_text {
+        if chunk.keyword == RECIPE_KEYWORD {
+            let text = chunk.get_text().map_err(|e| format!("Failed to read PNG recipe: {}", e))?;
+            let recipe = serde_json::from_str(&text).map_err(|e| format!("Failed to parse PNG recipe: {}", e))?;
+            return Ok(Some(recipe));
+        }
+    }
+    Ok(None)
+}
+
+// The tag sits in the IFD, often after the image data; the decoder bounds its size
+fn read_tiff_recipe(path: &Path) -> Result<Option<GrainRecipe>, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    let tiff_err = |e: tiff::TiffError| format!("Failed to read TIFF {:?}: {}", path, e);
+    let mut decoder = tiff::decoder::Decoder::new(BufReader::new(file)).map_err(tiff_err)?;
+    match decoder.find_tag(Tag::Unknown(TIFF_XMP_TAG)).map_err(tiff_err)? {
+        Some(value) => {
+            // The decoder widens BYTE values to integers
+            let bytes: Vec<u8> = value.into_u64_vec().map_err(tiff_err)?.into_iter().map(|b| b as u8).collect();
+            Ok(read_xmp_recipe(&bytes))
+        }
+        None => Ok(None),
+    }
+}
+
+fn read_xmp_recipe(bytes: &[u8]) -> Option<GrainRecipe> {
+    let marker = b"fgp:Recipe=\"";
+    let start = bytes.windows(marker.len()).position(|w| w == marker)? + marker.len();
+    let len = bytes[start..].iter().position(|&b| b == b'"')?;
+    let escaped = std::str::from_utf8(&bytes[start..start + len]).ok()?;
+    serde_json::from_str(&xml_unescape(escaped)).ok()
+}
+
+#[tauri::command]
+pub async fn read_image_recipe(app: tauri::AppHandle, path: String) -> Result<GrainPreset, String> {
+    let path = Path::new(&path);
+    // Only files the user dropped on the window, which the fs plugin grants to its scope
+    if !app.fs_scope().is_allowed(path) {
+        return Err(format!("{:?} was not dropped on the window", path));
+    }
+    let recipe = read_recipe(path)?;
+    let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("Restored").to_string();
+
+    Ok(GrainPreset {
+        format_version: PRESET_FORMAT_VERSION,
+        name,
+        description: format!("Restored from {} ({})", path.display(), recipe.software()),
+        params: recipe.params,
+    })
+}
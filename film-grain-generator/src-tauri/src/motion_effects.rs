@@ -0,0 +1,218 @@
+use image::RgbaImage;
+use rayon::prelude::*;
+
+use crate::color_response::luminance;
+use crate::halation::gaussian_blur;
+use crate::{film_format, lerp, split_mix64, GrainParams};
+
+// Bloom gathers tones from here up, more the brighter they are
+const BLOOM_THRESHOLD: f32 = 0.6;
+// Diffusion spreads over this share of the frame's long side, whatever the film format
+const BLOOM_SPREAD: f32 = 0.015;
+
+// Weave drifts over about half a second at 24 fps, with a faster jitter on top
+const WEAVE_DRIFT_FRAMES: u32 = 12;
+const WEAVE_JITTER_FRAMES: u32 = 3;
+const WEAVE_JITTER_SHARE: f32 = 0.25;
+// Film bounces vertically less than it weaves side to side
+const WEAVE_VERTICAL_SCALE: f32 = 0.6;
+// Shrunken old film sits looser on the registration pins: extra weave per year of age
+const WEAVE_PER_YEAR: f32 = 0.05;
+const MAX_AGE_WEAVE: f32 = 1.5;
+// Keeps the weave path apart from the grain seed
+const WEAVE_SEED_SALT: u64 = 0x6a7e_3eae;
+
+// Soft glow of a diffusion filter or the lens around the photo's highlights, in their own colour
+#[derive(Debug, Clone, Copy)]
+pub struct Bloom {
+    pub strength: f32,
+    pub sigma_px: f32,
+}
+
+impl Bloom {
+    // None unless bloom is turned on
+    pub fn for_params(params: &GrainParams) -> Option<Self> {
+        let strength = params.bloom_strength.filter(|&strength| strength > 0.0)?;
+        Some(Self { strength, sigma_px: (params.width.max(params.height) as f32 * BLOOM_SPREAD).max(1.0) })
+    }
+}
+
+// Spreads each channel's highlights and screens them back over the photo
+pub fn apply_bloom(photo: &mut RgbaImage, bloom: &Bloom) {
+    let (width, height) = (photo.width() as usize, photo.height() as usize);
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    for channel in 0..3 {
+        let mut glow: Vec<f32> = photo
+            .as_raw()
+            .par_chunks_exact(4)
+            .map(|pixel| {
+                let rgb = [pixel[0], pixel[1], pixel[2]].map(|c| c as f32 / 255.0);
+                rgb[channel] * ((luminance(rgb) - BLOOM_THRESHOLD) / (1.0 - BLOOM_THRESHOLD)).max(0.0)
+            })
+            .collect();
+        if glow.iter().all(|&g| g == 0.0) {
+            continue;
+        }
+        gaussian_blur(&mut glow, width, height, bloom.sigma_px);
+
+        photo.as_mut().par_chunks_exact_mut(4).zip(glow.par_iter()).for_each(|(pixel, &glow)| {
+            let light = (glow * bloom.strength).min(1.0);
+            let base = pixel[channel] as f32 / 255.0;
+            pixel[channel] = ((1.0 - (1.0 - base) * (1.0 - light)) * 255.0).round() as u8;
+        });
+    }
+}
+
+// Where one frame of a sequence sits as the film weaves through the gate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GateWeave {
+    pub offset_x: f32,
+    pub offset_y: f32,
+}
+
+impl GateWeave {
+    // The offset for the params' frame, or None unless weave is turned on. The path depends
+    // only on the seed, so frames rendered one by one with the same seed weave smoothly.
+    pub fn for_params(params: &GrainParams) -> Result<Option<Self>, String> {
+        let Some(amount) = params.gate_weave.filter(|&amount| amount > 0.0) else { return Ok(None) };
+
+        let format = params.film_format.as_deref().and_then(film_format::find_film_format).unwrap_or(&film_format::FILM_FORMATS[0]);
+        let long_side = params.width.max(params.height).max(1) as f32;
+        let microns_per_pixel = film_format::microns_per_pixel(params)?
+            .unwrap_or(format.gate_width_mm.max(format.gate_height_mm) * 1000.0 / long_side);
+        let age_factor = (1.0 + WEAVE_PER_YEAR * params.film_age_years.unwrap_or(0.0)).min(MAX_AGE_WEAVE);
+        let amplitude_px = format.gate_weave_um * amount * age_factor / microns_per_pixel;
+
+        let (seed, frame) = (params.seed.unwrap_or(0) ^ WEAVE_SEED_SALT, params.frame_index.unwrap_or(0));
+        Ok(Some(Self {
+            offset_x: amplitude_px * weave_path(seed, frame),
+            offset_y: amplitude_px * WEAVE_VERTICAL_SCALE * weave_path(split_mix64(seed), frame),
+        }))
+    }
+}
+
+// Seed for the params' grain. Each frame of a sequence gets fresh grain, as every frame of
+// film has its own crystals, while frame 0 keeps the seed's single-image grain.
+pub fn grain_seed(params: &GrainParams) -> u64 {
+    let seed = params.seed.unwrap_or(0);
+    match params.frame_index.unwrap_or(0) {
+        0 => seed,
+        frame => split_mix64(seed ^ frame as u64),
+    }
+}
+
+// Slow drift plus jitter, each smoothly interpolated between seeded values: -1 to 1
+fn weave_path(seed: u64, frame: u32) -> f32 {
+    let drift = value_noise(seed, frame, WEAVE_DRIFT_FRAMES);
+    let jitter = value_noise(split_mix64(seed ^ 1), frame, WEAVE_JITTER_FRAMES);
+    drift * (1.0 - WEAVE_JITTER_SHARE) + jitter * WEAVE_JITTER_SHARE
+}
+
+fn value_noise(seed: u64, frame: u32, period: u32) -> f32 {
+    let value = |key: u32| (split_mix64(seed ^ key as u64) >> 40) as f32 / (1u64 << 24) as f32 * 2.0 - 1.0;
+    let (key, t) = (frame / period, (frame % period) as f32 / period as f32);
+    lerp(value(key), value(key + 1), t * t * (3.0 - 2.0 * t))
+}
+
+// Moves the whole frame, grain and all, by the weave's sub-pixel offset. Edges repeat
+// the outermost pixels, as a gate's soft frame line would hide them anyway.
+pub fn apply_gate_weave(photo: &mut RgbaImage, weave: &GateWeave) {
+    let (width, height) = (photo.width() as usize, photo.height() as usize);
+    if width == 0 || height == 0 || (weave.offset_x == 0.0 && weave.offset_y == 0.0) {
+        return;
+    }
+
+    let source = photo.as_raw().clone();
+    let pixel = |x: isize, y: isize| {
+        let at = (y.clamp(0, height as isize - 1) as usize * width + x.clamp(0, width as isize - 1) as usize) * 4;
+        &source[at..at + 4]
+    };
+    let (shift_x, shift_y) = (weave.offset_x.floor(), weave.offset_y.floor());
+    let (fx, fy) = (weave.offset_x - shift_x, weave.offset_y - shift_y);
+    photo.as_mut().par_chunks_exact_mut(width * 4).enumerate().for_each(|(y, row)| {
+        let source_y = y as isize - shift_y as isize;
+        for (x, out) in row.chunks_exact_mut(4).enumerate() {
+            let source_x = x as isize - shift_x as isize;
+            // Destination (x, y) shows the source point offset up-left by the weave
+            let (top_left, top_right) = (pixel(source_x - 1, source_y - 1), pixel(source_x, source_y - 1));
+            let (bottom_left, bottom_right) = (pixel(source_x - 1, source_y), pixel(source_x, source_y));
+            for channel in 0..4 {
+                let top = lerp(top_right[channel] as f32, top_left[channel] as f32, fx);
+                let bottom = lerp(bottom_right[channel] as f32, bottom_left[channel] as f32, fx);
+                out[channel] = lerp(bottom, top, fy).round() as u8;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weave_drifts_smoothly_and_repeats_for_a_seed() {
+        let path: Vec<f32> = (0..200).map(|frame| weave_path(42, frame)).collect();
+        assert_eq!(path, (0..200).map(|frame| weave_path(42, frame)).collect::<Vec<_>>());
+        assert!(path.iter().all(|v| v.abs() <= 1.0));
+        assert!(path.windows(2).all(|pair| (pair[1] - pair[0]).abs() < 0.5));
+        assert!(path.iter().any(|v| v.abs() > 0.2));
+        assert_ne!(weave_path(43, 5), path[5]);
+    }
+
+    #[test]
+    fn frames_get_fresh_grain_but_a_continuous_weave() {
+        let mut params: GrainParams = serde_json::from_str(concat!(
+            r#"{"film_stock":"Kodak Tri-X 400","exposure_compensation":0,"size_multiplier":1,"contrast":100,"#,
+            r#""grain_density":1000,"width":640,"height":480,"background":"transparent","seed":7,"gate_weave":1}"#
+        ))
+        .unwrap();
+        assert_eq!(grain_seed(&params), 7);
+        let frames: Vec<(u64, GateWeave)> = (1..40)
+            .map(|frame| {
+                params.frame_index = Some(frame);
+                (grain_seed(&params), GateWeave::for_params(&params).unwrap().unwrap())
+            })
+            .collect();
+        assert!(frames.windows(2).all(|pair| pair[0].0 != pair[1].0 && pair[0].0 != 7));
+        let amplitude = frames.iter().map(|(_, weave)| weave.offset_x.abs()).fold(0.0, f32::max);
+        assert!(amplitude > 0.0);
+        assert!(frames.windows(2).all(|pair| (pair[1].1.offset_x - pair[0].1.offset_x).abs() < amplitude * 0.5));
+
+        let grains = |params: &GrainParams| crate::generate_stock_grains(&crate::StockData::load(&params.film_stock).unwrap(), params).unwrap();
+        let next = GrainParams { frame_index: Some(40), ..params.clone() };
+        let (this_frame, next_frame) = (grains(&params), grains(&next));
+        assert!(this_frame.iter().zip(&next_frame).any(|(a, b)| a.x != b.x || a.y != b.y));
+        assert_eq!(this_frame.iter().map(|grain| (grain.x, grain.y)).collect::<Vec<_>>(), grains(&params).iter().map(|grain| (grain.x, grain.y)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn weave_moves_the_frame_by_its_offset() {
+        let mut photo = RgbaImage::from_pixel(9, 9, image::Rgba([0, 0, 0, 255]));
+        photo.put_pixel(4, 4, image::Rgba([200, 100, 50, 255]));
+        let mut moved = photo.clone();
+        apply_gate_weave(&mut moved, &GateWeave { offset_x: 2.0, offset_y: -1.0 });
+        assert_eq!(moved.get_pixel(6, 3), photo.get_pixel(4, 4));
+        assert_eq!(moved.get_pixel(4, 4)[0], 0);
+
+        // Half a pixel shares it between two
+        let mut half = photo.clone();
+        apply_gate_weave(&mut half, &GateWeave { offset_x: 0.5, offset_y: 0.0 });
+        assert_eq!((half.get_pixel(4, 4)[0], half.get_pixel(5, 4)[0]), (100, 100));
+    }
+
+    #[test]
+    fn bloom_only_lights_up_around_highlights() {
+        let mut photo = RgbaImage::from_pixel(40, 40, image::Rgba([30, 30, 30, 255]));
+        photo.put_pixel(20, 20, image::Rgba([255, 240, 200, 255]));
+        let mut dark = RgbaImage::from_pixel(40, 40, image::Rgba([120, 90, 60, 255]));
+        let bloom = Bloom { strength: 4.0, sigma_px: 2.0 };
+        apply_bloom(&mut photo, &bloom);
+        apply_bloom(&mut dark, &bloom);
+        assert!(dark.pixels().all(|p| p.0 == [120, 90, 60, 255]));
+        assert!(photo.get_pixel(22, 20)[0] > 30 && photo.get_pixel(22, 20)[0] >= photo.get_pixel(22, 20)[2]);
+        assert_eq!(photo.get_pixel(2, 2)[0], 30);
+    }
+}
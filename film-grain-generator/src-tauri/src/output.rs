@@ -0,0 +1,635 @@
+use image::{DynamicImage, RgbaImage};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Cursor, ErrorKind, Write};
+use std::path::{Component, Path, PathBuf};
+use tauri_plugin_fs::FsExt;
+
+use crate::metadata::{self, GrainRecipe};
+
+const DEFAULT_JPEG_QUALITY: u8 = 95;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Png,
+    Jpeg,
+    Tiff,
+    Webp,
+    Exr,
+}
+
+impl OutputFormat {
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" => Some(Self::Jpeg),
+            "tif" | "tiff" => Some(Self::Tiff),
+            "webp" => Some(Self::Webp),
+            "exr" => Some(Self::Exr),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+            Self::Tiff => "tif",
+            Self::Webp => "webp",
+            Self::Exr => "exr",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TiffCompression {
+    None,
+    #[default]
+    Lzw,
+    Deflate,
+    Packbits,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverwritePolicy {
+    #[default]
+    Fail,    // Refuse to touch an existing file
+    Replace, // Overwrite it
+    Rename,  // Keep both: "name (1).png", "name (2).png", ...
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct OutputOptions {
+    pub format: Option<OutputFormat>, // None = from the file extension, PNG if there is none
+    pub jpeg_quality: u8,
+    pub tiff_compression: TiffCompression,
+    pub overwrite: OverwritePolicy,
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        Self {
+            format: None,
+            jpeg_quality: DEFAULT_JPEG_QUALITY,
+            tiff_compression: TiffCompression::default(),
+            overwrite: OverwritePolicy::default(),
+        }
+    }
+}
+
+impl OutputOptions {
+    pub fn resolved_format(&self, path: &Path) -> OutputFormat {
+        self.format
+            .or_else(|| OutputFormat::from_extension(path))
+            .unwrap_or(OutputFormat::Png)
+    }
+}
+
+// Save errors reach the webview as { kind, message } so it can react to e.g. an existing file
+#[derive(Debug)]
+pub enum OutputError {
+    InvalidFileName(String),  // Not a plain file name: separators, "..", control characters, ...
+    OutsideDirectory(PathBuf), // Resolves (e.g. through a symlink) outside the target directory
+    NotPermitted(PathBuf),     // Absolute path the user did not pick in the save dialog
+    AlreadyExists(PathBuf),    // Needs confirmation before it is replaced
+    Io(PathBuf, std::io::Error),
+    Image(String),             // Decoding, encoding or metadata failures
+}
+
+impl OutputError {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::InvalidFileName(_) => "invalidFileName",
+            Self::OutsideDirectory(_) => "outsideDirectory",
+            Self::NotPermitted(_) => "notPermitted",
+            Self::AlreadyExists(_) => "alreadyExists",
+            Self::Io(..) => "io",
+            Self::Image(_) => "image",
+        }
+    }
+}
+
+impl fmt::Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidFileName(reason) => write!(f, "Invalid file name: {}", reason),
+            Self::OutsideDirectory(path) => write!(f, "{:?} is outside the output directory", path),
+            Self::NotPermitted(path) => write!(f, "Output path {:?} was not chosen through the save dialog", path),
+            Self::AlreadyExists(path) => write!(f, "{:?} already exists", path),
+            Self::Io(path, e) => write!(f, "Failed to write {:?}: {}", path, e),
+            Self::Image(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for OutputError {}
+
+impl Serialize for OutputError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("OutputError", 2)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+impl From<String> for OutputError {
+    fn from(message: String) -> Self {
+        Self::Image(message)
+    }
+}
+
+impl From<&str> for OutputError {
+    fn from(message: &str) -> Self {
+        Self::Image(message.to_string())
+    }
+}
+
+impl From<OutputError> for String {
+    fn from(error: OutputError) -> Self {
+        error.to_string()
+    }
+}
+
+// Opens the file the webview asked for, before anything is rendered. Bare file names keep
+// the old behaviour of landing in Downloads; anything else must be a path the user picked
+// in the save dialog, which the dialog plugin adds to the fs scope. The scope is checked on
+// the file actually written - with the format's extension and any "(n)" from renaming.
+pub fn open_requested_path(app: &tauri::AppHandle, path: &str, options: &OutputOptions) -> Result<OutputFile, OutputError> {
+    let requested = Path::new(path);
+
+    if requested.is_absolute() {
+        let (dir, name) = match (requested.parent(), requested.file_name().and_then(|n| n.to_str())) {
+            (Some(dir), Some(name)) => (dir, name),
+            _ => return Err(OutputError::InvalidFileName(format!("{:?} does not name a file", requested))),
+        };
+        let scope = app.fs_scope();
+        // Candidates are in the canonical directory; the scope holds the path as picked
+        let permitted = |candidate: &Path| candidate.file_name().is_some_and(|name| scope.is_allowed(dir.join(name)));
+        return OutputFile::create(&resolve_in_dir(dir, name)?, options, permitted);
+    }
+
+    let downloads_dir = dirs::download_dir()
+        .ok_or_else(|| OutputError::Image("Could not find Downloads directory".to_string()))?;
+    OutputFile::create(&resolve_in_dir(&downloads_dir, path)?, options, |_| true)
+}
+
+// Rejects anything but a plain file name, then checks on the canonical directory that the
+// result cannot escape it - `..`, absolute paths and symlinks pointing elsewhere included.
+pub fn resolve_in_dir(dir: &Path, name: &str) -> Result<PathBuf, OutputError> {
+    validate_file_name(name)?;
+
+    let dir = dir.canonicalize().map_err(|e| OutputError::Io(dir.to_path_buf(), e))?;
+    let path = dir.join(name);
+    if path.parent() != Some(dir.as_path()) {
+        return Err(OutputError::OutsideDirectory(path));
+    }
+
+    // An existing entry must not be a link to somewhere else
+    if let Ok(meta) = std::fs::symlink_metadata(&path) {
+        let target = path.canonicalize().map_err(|e| OutputError::Io(path.clone(), e))?;
+        if meta.file_type().is_symlink() || !target.starts_with(&dir) {
+            return Err(OutputError::OutsideDirectory(path));
+        }
+    }
+    Ok(path)
+}
+
+fn validate_file_name(name: &str) -> Result<(), OutputError> {
+    let invalid = |reason: &str| Err(OutputError::InvalidFileName(format!("{:?} {}", name, reason)));
+
+    if name.trim().is_empty() {
+        return invalid("is empty");
+    }
+    if name.len() > 255 {
+        return invalid("is too long");
+    }
+    // Checked on every platform: a name valid here must also be valid on Windows
+    if name.contains(['/', '\\']) {
+        return invalid("contains a path separator");
+    }
+    if name.chars().any(|c| c.is_control() || matches!(c, '<' | '>' | ':' | '"' | '|' | '?' | '*')) {
+        return invalid("contains characters that are not allowed in file names");
+    }
+    if name.starts_with('.') {
+        return invalid("would be a hidden file");
+    }
+    if !matches!(Path::new(name).components().collect::<Vec<_>>().as_slice(), [Component::Normal(_)]) {
+        return invalid("is not a plain file name");
+    }
+    Ok(())
+}
+
+// Applies the format's extension
+pub fn prepare_output_path(path: &Path, options: &OutputOptions) -> PathBuf {
+    let format = options.resolved_format(path);
+    let mut path = path.to_path_buf();
+    if OutputFormat::from_extension(&path) != Some(format) {
+        path.set_extension(format.extension());
+    }
+    path
+}
+
+// An output file opened ahead of rendering, so a refused or existing path fails before any
+// work is done. Dropped without being written, it is removed again.
+pub struct OutputFile {
+    path: PathBuf,
+    format: OutputFormat,
+    file: Option<File>,
+}
+
+impl OutputFile {
+    // Applies the format's extension and opens the file by the overwrite policy. Only paths
+    // `permitted` allows are ever created, renamed ones included.
+    pub fn create(path: &Path, options: &OutputOptions, permitted: impl Fn(&Path) -> bool) -> Result<Self, OutputError> {
+        let format = options.resolved_format(path);
+        let (path, file) = create_output_file(&prepare_output_path(path, options), options, &permitted)?;
+        Ok(Self { path, format, file: Some(file) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Runs the encoder on the file, returning the path written
+    fn write_with(mut self, encode: impl FnOnce(&mut BufWriter<File>, OutputFormat) -> Result<(), OutputError>) -> Result<PathBuf, OutputError> {
+        let mut out = BufWriter::new(self.file.take().ok_or_else(|| OutputError::Image("Output file was already written".to_string()))?);
+        encode(&mut out, self.format)?;
+        out.flush().map_err(|e| OutputError::Io(self.path.clone(), e))?;
+        Ok(std::mem::take(&mut self.path))
+    }
+}
+
+impl Drop for OutputFile {
+    // Don't leave an empty or half-written file behind
+    fn drop(&mut self) {
+        if !self.path.as_os_str().is_empty() {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+// Opens the output file according to the overwrite policy. Fail and Rename use create_new,
+// so a file appearing between the check and the write is never clobbered.
+fn create_output_file(path: &Path, options: &OutputOptions, permitted: &dyn Fn(&Path) -> bool) -> Result<(PathBuf, File), OutputError> {
+    let create_new = |path: &Path| OpenOptions::new().write(true).create_new(true).open(path);
+    if !permitted(path) {
+        return Err(OutputError::NotPermitted(path.to_path_buf()));
+    }
+
+    match options.overwrite {
+        OverwritePolicy::Replace => {
+            if std::fs::symlink_metadata(path).map(|m| m.file_type().is_symlink()).unwrap_or(false) {
+                return Err(OutputError::OutsideDirectory(path.to_path_buf()));
+            }
+            let file = File::create(path).map_err(|e| OutputError::Io(path.to_path_buf(), e))?;
+            Ok((path.to_path_buf(), file))
+        }
+        OverwritePolicy::Fail => match create_new(path) {
+            Ok(file) => Ok((path.to_path_buf(), file)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => Err(OutputError::AlreadyExists(path.to_path_buf())),
+            Err(e) => Err(OutputError::Io(path.to_path_buf(), e)),
+        },
+        OverwritePolicy::Rename => {
+            let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("grain").to_string();
+            let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_string();
+            let candidates = std::iter::once(path.to_path_buf())
+                .chain((1..10_000).map(|n| path.with_file_name(format!("{} ({}).{}", stem, n, extension))));
+
+            for candidate in candidates {
+                if !permitted(&candidate) {
+                    return Err(OutputError::NotPermitted(candidate));
+                }
+                match create_new(&candidate) {
+                    Ok(file) => return Ok((candidate, file)),
+                    Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                    Err(e) => return Err(OutputError::Io(candidate, e)),
+                }
+            }
+            Err(OutputError::AlreadyExists(path.to_path_buf()))
+        }
+    }
+}
+
+// Encodes and writes the image, embedding the recipe for PNG, JPEG and TIFF.
+// WebP and EXR are written without it. Returns the path actually written.
+pub fn write_image(img: &RgbaImage, out: OutputFile, options: &OutputOptions, recipe: Option<&GrainRecipe>) -> Result<PathBuf, OutputError> {
+    out.write_with(|out, format| match format {
+        OutputFormat::Png => write_png(img, out, recipe),
+        OutputFormat::Jpeg => write_jpeg(img, out, options.jpeg_quality, recipe),
+        OutputFormat::Tiff => write_tiff(img, out, options.tiff_compression, recipe),
+        OutputFormat::Webp => image::codecs::webp::WebPEncoder::new_lossless(out)
+            .encode(img.as_raw(), img.width(), img.height(), image::ExtendedColorType::Rgba8)
+            .map_err(|e| OutputError::Image(format!("Failed to encode WebP: {}", e))),
+        OutputFormat::Exr => DynamicImage::ImageRgba8(img.clone())
+            .to_rgba32f()
+            .write_to(out, image::ImageFormat::OpenExr)
+            .map_err(|e| OutputError::Image(format!("Failed to encode EXR: {}", e))),
+    })
+}
+
+// Receives the image as consecutive bands of full RGBA rows, top to bottom
+pub type RowSink<'a> = dyn FnMut(&[u8]) -> Result<(), String> + 'a;
+
+// Like write_image, but the pixels are produced band by band by `render` and streamed
+// straight into the encoder, so the full image never has to exist in memory.
+// Only PNG and TIFF can be written this way.
+pub fn write_streamed<F>(width: u32, height: u32, out: OutputFile, options: &OutputOptions, recipe: Option<&GrainRecipe>, render: F) -> Result<PathBuf, OutputError>
+where
+    F: FnOnce(&mut RowSink) -> Result<(), String>,
+{
+    if !matches!(out.format, OutputFormat::Png | OutputFormat::Tiff) {
+        return Err(OutputError::Image(format!("{:?} cannot be streamed - export very large canvases as PNG or TIFF", out.format)));
+    }
+
+    out.write_with(|out, format| match format {
+        OutputFormat::Png => stream_png(width, height, out, recipe, render),
+        _ => stream_tiff(width, height, out, options.tiff_compression, recipe, render),
+    })
+}
+
+fn stream_png<W: Write, F>(width: u32, height: u32, out: W, recipe: Option<&GrainRecipe>, render: F) -> Result<(), OutputError>
+where
+    F: FnOnce(&mut RowSink) -> Result<(), String>,
+{
+    let mut encoder = png::Encoder::new(out, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    if let Some(recipe) = recipe {
+        metadata::add_png_chunks(&mut encoder, recipe)?;
+    }
+
+    let mut writer = encoder.write_header().map_err(|e| format!("Failed to write PNG header: {}", e))?;
+    let mut stream = writer.stream_writer().map_err(|e| format!("Failed to start PNG data: {}", e))?;
+    render(&mut |rows: &[u8]| stream.write_all(rows).map_err(|e| format!("Failed to write PNG data: {}", e)))?;
+    stream.finish().map_err(|e| format!("Failed to write PNG data: {}", e))?;
+    writer.finish().map_err(|e| OutputError::Image(format!("Failed to finish PNG: {}", e)))
+}
+
+fn stream_tiff<W: Write + std::io::Seek, F>(width: u32, height: u32, out: &mut W, compression: TiffCompression, recipe: Option<&GrainRecipe>, render: F) -> Result<(), OutputError>
+where
+    F: FnOnce(&mut RowSink) -> Result<(), String>,
+{
+    use tiff::encoder::compression::{Deflate, Lzw, Packbits, Uncompressed};
+
+    match compression {
+        TiffCompression::None => stream_tiff_with(width, height, out, Uncompressed, recipe, render),
+        TiffCompression::Lzw => stream_tiff_with(width, height, out, Lzw, recipe, render),
+        TiffCompression::Deflate => stream_tiff_with(width, height, out, Deflate::default(), recipe, render),
+        TiffCompression::Packbits => stream_tiff_with(width, height, out, Packbits, recipe, render),
+    }
+}
+
+const TIFF_ROWS_PER_STRIP: u32 = 64;
+
+fn stream_tiff_with<W, D, F>(width: u32, height: u32, out: &mut W, compression: D, recipe: Option<&GrainRecipe>, render: F) -> Result<(), OutputError>
+where
+    W: Write + std::io::Seek,
+    D: tiff::encoder::compression::Compression,
+    F: FnOnce(&mut RowSink) -> Result<(), String>,
+{
+    use tiff::encoder::{colortype, TiffEncoder};
+
+    let tiff_err = |e: tiff::TiffError| format!("Failed to write TIFF: {}", e);
+    let mut encoder = TiffEncoder::new(out).map_err(tiff_err)?;
+    let mut image = encoder
+        .new_image_with_compression::<colortype::RGBA8, D>(width, height, compression)
+        .map_err(tiff_err)?;
+    image.rows_per_strip(TIFF_ROWS_PER_STRIP).map_err(tiff_err)?;
+
+    if let Some(recipe) = recipe {
+        metadata::add_tiff_tags(image.encoder(), recipe)?;
+    }
+
+    // Bands don't line up with strips, so rows wait here until a whole strip is ready
+    let mut pending = Vec::new();
+    render(&mut |rows: &[u8]| {
+        pending.extend_from_slice(rows);
+        loop {
+            let strip_len = image.next_strip_sample_count() as usize;
+            if strip_len == 0 || pending.len() < strip_len {
+                return Ok(());
+            }
+            image.write_strip(&pending[..strip_len]).map_err(tiff_err)?;
+            pending.drain(..strip_len);
+        }
+    })?;
+
+    image.finish().map_err(|e| OutputError::Image(tiff_err(e)))
+}
+
+fn write_png<W: Write>(img: &RgbaImage, out: W, recipe: Option<&GrainRecipe>) -> Result<(), OutputError> {
+    let mut encoder = png::Encoder::new(out, img.width(), img.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    if let Some(recipe) = recipe {
+        metadata::add_png_chunks(&mut encoder, recipe)?;
+    }
+
+    let mut writer = encoder.write_header().map_err(|e| format!("Failed to write PNG header: {}", e))?;
+    writer.write_image_data(img.as_raw()).map_err(|e| format!("Failed to write PNG data: {}", e))?;
+    writer.finish().map_err(|e| OutputError::Image(format!("Failed to finish PNG: {}", e)))
+}
+
+fn write_jpeg<W: Write>(img: &RgbaImage, out: &mut W, quality: u8, recipe: Option<&GrainRecipe>) -> Result<(), OutputError> {
+    // JPEG has no alpha channel
+    let rgb = DynamicImage::ImageRgba8(img.clone()).to_rgb8();
+    let mut jpeg = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut Cursor::new(&mut jpeg), quality.clamp(1, 100))
+        .encode_image(&rgb)
+        .map_err(|e| format!("Failed to encode JPEG: {}", e))?;
+
+    let segment = match recipe {
+        Some(recipe) => metadata::jpeg_xmp_segment(recipe)?,
+        None => Vec::new(),
+    };
+
+    // The XMP APP1 segment goes after SOI and, as JFIF requires APP0 to come first, after
+    // the encoder's JFIF APP0 segment when there is one
+    let insert_at = match jpeg.get(2..6) {
+        Some([0xFF, 0xE0, high, low]) => 4 + u16::from_be_bytes([*high, *low]) as usize,
+        _ => 2,
+    }
+    .min(jpeg.len());
+    let write_err = |e: std::io::Error| OutputError::Image(format!("Failed to write JPEG: {}", e));
+    out.write_all(&jpeg[..insert_at]).map_err(write_err)?;
+    out.write_all(&segment).map_err(write_err)?;
+    out.write_all(&jpeg[insert_at..]).map_err(write_err)
+}
+
+fn write_tiff<W: Write + std::io::Seek>(img: &RgbaImage, out: &mut W, compression: TiffCompression, recipe: Option<&GrainRecipe>) -> Result<(), OutputError> {
+    use tiff::encoder::compression::{Deflate, Lzw, Packbits, Uncompressed};
+
+    match compression {
+        TiffCompression::None => write_tiff_with(img, out, Uncompressed, recipe),
+        TiffCompression::Lzw => write_tiff_with(img, out, Lzw, recipe),
+        TiffCompression::Deflate => write_tiff_with(img, out, Deflate::default(), recipe),
+        TiffCompression::Packbits => write_tiff_with(img, out, Packbits, recipe),
+    }
+}
+
+fn write_tiff_with<W: Write + std::io::Seek, D: tiff::encoder::compression::Compression>(img: &RgbaImage, out: &mut W, compression: D, recipe: Option<&GrainRecipe>) -> Result<(), OutputError> {
+    use tiff::encoder::{colortype, TiffEncoder};
+
+    let tiff_err = |e: tiff::TiffError| OutputError::Image(format!("Failed to write TIFF: {}", e));
+    let mut encoder = TiffEncoder::new(out).map_err(tiff_err)?;
+    let mut image = encoder
+        .new_image_with_compression::<colortype::RGBA8, D>(img.width(), img.height(), compression)
+        .map_err(tiff_err)?;
+
+    if let Some(recipe) = recipe {
+        metadata::add_tiff_tags(image.encoder(), recipe)?;
+    }
+    image.write_data(img.as_raw()).map_err(tiff_err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("fgp-output-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn save(img: &RgbaImage, path: &Path, options: &OutputOptions, recipe: Option<&GrainRecipe>) -> Result<PathBuf, OutputError> {
+        write_image(img, OutputFile::create(path, options, |_| true)?, options, recipe)
+    }
+
+    #[test]
+    fn accepts_plain_file_names() {
+        let dir = scratch_dir("plain");
+        let path = resolve_in_dir(&dir, "grain_Kodak_Portra_400 2024.png").unwrap();
+        assert_eq!(path, dir.canonicalize().unwrap().join("grain_Kodak_Portra_400 2024.png"));
+    }
+
+    #[test]
+    fn rejects_traversal_and_malformed_names() {
+        let dir = scratch_dir("malicious");
+        let malicious = [
+            "../../.bashrc",
+            "..",
+            ".",
+            "",
+            "   ",
+            "/etc/passwd",
+            "sub/../../grain.png",
+            "..\\..\\grain.png",
+            "C:\\Windows\\grain.png",
+            "grain\0.png",
+            "grain\n.png",
+            ".bashrc",
+            "con:grain.png",
+        ];
+
+        for name in malicious {
+            match resolve_in_dir(&dir, name) {
+                Err(OutputError::InvalidFileName(_)) => {}
+                other => panic!("{:?} was not rejected: {:?}", name, other),
+            }
+        }
+        assert!(resolve_in_dir(&dir, &"a".repeat(300)).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlinks_leading_out_of_the_directory() {
+        let dir = scratch_dir("symlink");
+        let outside = scratch_dir("symlink-target").join("victim.png");
+        std::fs::write(&outside, b"keep me").unwrap();
+        std::os::unix::fs::symlink(&outside, dir.join("grain.png")).unwrap();
+
+        assert!(matches!(resolve_in_dir(&dir, "grain.png"), Err(OutputError::OutsideDirectory(_))));
+
+        // Even a direct write with Replace must not follow the link
+        let options = OutputOptions { overwrite: OverwritePolicy::Replace, ..Default::default() };
+        let img = RgbaImage::new(4, 4);
+        assert!(save(&img, &dir.join("grain.png"), &options, None).is_err());
+        assert_eq!(std::fs::read(&outside).unwrap(), b"keep me");
+    }
+
+    #[test]
+    fn refuses_to_overwrite_without_confirmation() {
+        let dir = scratch_dir("overwrite");
+        let path = dir.join("grain.png");
+        std::fs::write(&path, b"existing").unwrap();
+        let img = RgbaImage::new(4, 4);
+
+        let result = save(&img, &path, &OutputOptions::default(), None);
+        assert!(matches!(result, Err(OutputError::AlreadyExists(_))));
+        assert_eq!(std::fs::read(&path).unwrap(), b"existing");
+
+        let renamed = OutputOptions { overwrite: OverwritePolicy::Rename, ..Default::default() };
+        assert_eq!(save(&img, &path, &renamed, None).unwrap(), dir.join("grain (1).png"));
+
+        let replace = OutputOptions { overwrite: OverwritePolicy::Replace, ..Default::default() };
+        assert_eq!(save(&img, &path, &replace, None).unwrap(), path);
+        assert_ne!(std::fs::read(&path).unwrap(), b"existing");
+    }
+
+    #[test]
+    fn checks_permission_on_the_file_actually_written() {
+        let dir = scratch_dir("permitted");
+        let picked = dir.join("grain.png");
+        let permitted = |path: &Path| path == picked;
+
+        // A format that changes the extension leaves the picked file
+        let tiff = OutputOptions { format: Some(OutputFormat::Tiff), ..Default::default() };
+        assert!(matches!(OutputFile::create(&picked, &tiff, permitted), Err(OutputError::NotPermitted(path)) if path == dir.join("grain.tif")));
+
+        // So does renaming around an existing file
+        std::fs::write(&picked, b"existing").unwrap();
+        let rename = OutputOptions { overwrite: OverwritePolicy::Rename, ..Default::default() };
+        assert!(matches!(OutputFile::create(&picked, &rename, permitted), Err(OutputError::NotPermitted(path)) if path == dir.join("grain (1).png")));
+        assert!(!dir.join("grain (1).png").exists());
+
+        // Opened but never written, the file goes away again
+        std::fs::remove_file(&picked).unwrap();
+        let out = OutputFile::create(&picked, &OutputOptions::default(), permitted).unwrap();
+        assert!(out.path().exists());
+        drop(out);
+        assert!(!picked.exists());
+    }
+
+    #[test]
+    fn recipes_read_back_from_every_format_with_metadata() {
+        let dir = scratch_dir("recipes");
+        let params: crate::GrainParams = serde_json::from_str(concat!(
+            r#"{"film_stock":"Kodak Portra 400","exposure_compensation":0,"size_multiplier":1,"contrast":100,"#,
+            r#""grain_density":1000,"width":8,"height":8,"background":"transparent","seed":9007199254740991}"#
+        ))
+        .unwrap();
+        let recipe = GrainRecipe::new(&params);
+        let img = RgbaImage::from_pixel(8, 8, image::Rgba([90, 120, 150, 255]));
+
+        for name in ["grain.png", "grain.jpg", "grain.tiff"] {
+            let path = save(&img, &dir.join(name), &OutputOptions::default(), Some(&recipe)).unwrap();
+            let restored = metadata::read_recipe(&path).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert_eq!((restored.film_stock.as_str(), restored.seed), ("Kodak Portra 400", params.seed), "{}", name);
+            assert!(image::open(&path).is_ok(), "{} no longer decodes", name);
+        }
+
+        // JFIF's APP0 stays the first segment, with the XMP APP1 right behind it
+        let jpeg = std::fs::read(dir.join("grain.jpg")).unwrap();
+        assert_eq!(&jpeg[..4], &[0xFF, 0xD8, 0xFF, 0xE0]);
+        let app1 = 4 + u16::from_be_bytes([jpeg[4], jpeg[5]]) as usize;
+        assert_eq!(&jpeg[app1..app1 + 2], &[0xFF, 0xE1]);
+    }
+
+    #[test]
+    fn errors_serialise_with_a_kind() {
+        let json = serde_json::to_value(OutputError::AlreadyExists(PathBuf::from("grain.png"))).unwrap();
+        assert_eq!(json["kind"], "alreadyExists");
+        assert!(json["message"].as_str().unwrap().contains("grain.png"));
+    }
+}
@@ -0,0 +1,186 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::GrainParams;
+
+// Presets live as individual JSON files so they can be shared by just sending the file
+const PRESET_DIR_NAME: &str = "FilmGrainPro/presets";
+const PRESET_EXTENSION: &str = "json";
+pub const PRESET_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrainPreset {
+    #[serde(default = "default_format_version")]
+    pub format_version: u32,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub params: GrainParams,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PresetSummary {
+    name: String,
+    description: String,
+    film_stock: String,
+    path: String,
+}
+
+fn default_format_version() -> u32 {
+    PRESET_FORMAT_VERSION
+}
+
+pub fn preset_dir() -> Result<PathBuf, String> {
+    let config_dir = dirs::config_dir().ok_or("Could not find user config directory")?;
+    Ok(config_dir.join(PRESET_DIR_NAME))
+}
+
+// Preset names are free text, file names are not - keep letters, digits, dashes and underscores
+pub fn preset_file_stem(name: &str) -> Result<String, String> {
+    let stem: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+
+    if stem.trim_matches('_').is_empty() {
+        return Err(format!("Invalid preset name '{}'", name));
+    }
+    Ok(stem)
+}
+
+fn preset_path(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    Ok(dir.join(format!("{}.{}", preset_file_stem(name)?, PRESET_EXTENSION)))
+}
+
+pub fn read_preset_file(path: &Path) -> Result<GrainPreset, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read preset {:?}: {}", path, e))?;
+    let preset: GrainPreset = serde_json::from_str(&contents)
+        .map_err(|e| format!("Failed to parse preset {:?}: {}", path, e))?;
+
+    if preset.format_version > PRESET_FORMAT_VERSION {
+        return Err(format!("Preset {:?} was written by a newer version (format {})", path, preset.format_version));
+    }
+    Ok(preset)
+}
+
+pub fn write_preset(preset: &GrainPreset) -> Result<PathBuf, String> {
+    write_preset_in(&preset_dir()?, preset)
+}
+
+// Names that differ only in spaces or punctuation share a file, which may only be
+// overwritten by a preset of the same name
+fn write_preset_in(dir: &Path, preset: &GrainPreset) -> Result<PathBuf, String> {
+    let path = preset_path(dir, &preset.name)?;
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create preset directory: {}", e))?;
+    if let Ok(existing) = read_preset_file(&path) {
+        if existing.name != preset.name {
+            return Err(format!("Preset '{}' already uses {:?} - choose a different name", existing.name, path));
+        }
+    }
+
+    let mut preset = preset.clone();
+    preset.format_version = PRESET_FORMAT_VERSION;
+    let json = serde_json::to_string_pretty(&preset)
+        .map_err(|e| format!("Failed to serialise preset: {}", e))?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write preset {:?}: {}", path, e))?;
+
+    println!("💾 Saved preset '{}' to {:?}", preset.name, path);
+    Ok(path)
+}
+
+pub fn find_preset(name: &str) -> Result<GrainPreset, String> {
+    find_preset_in(&preset_dir()?, name)
+}
+
+fn find_preset_in(dir: &Path, name: &str) -> Result<GrainPreset, String> {
+    let preset = read_preset_file(&preset_path(dir, name)?)?;
+    if preset.name != name {
+        return Err(format!("Preset '{}' not found ({:?} holds '{}')", name, preset_path(dir, name)?, preset.name));
+    }
+    Ok(preset)
+}
+
+pub fn list_preset_files() -> Result<Vec<(PathBuf, GrainPreset)>, String> {
+    let dir = preset_dir()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let entries = std::fs::read_dir(&dir).map_err(|e| format!("Failed to read preset directory: {}", e))?;
+    let mut presets = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some(PRESET_EXTENSION) {
+            continue;
+        }
+        // Skip unreadable files rather than failing the whole listing
+        match read_preset_file(&path) {
+            Ok(preset) => presets.push((path, preset)),
+            Err(e) => println!("⚠️ Skipping preset: {}", e),
+        }
+    }
+
+    presets.sort_by_key(|(_, preset)| preset.name.to_lowercase());
+    Ok(presets)
+}
+
+#[tauri::command]
+pub async fn save_preset(preset: GrainPreset) -> Result<String, String> {
+    let path = write_preset(&preset)?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub async fn load_preset(name: String) -> Result<GrainPreset, String> {
+    find_preset(&name)
+}
+
+#[tauri::command]
+pub async fn list_presets() -> Result<Vec<PresetSummary>, String> {
+    Ok(list_preset_files()?
+        .into_iter()
+        .map(|(path, preset)| PresetSummary {
+            name: preset.name,
+            description: preset.description,
+            film_stock: preset.params.film_stock,
+            path: path.to_string_lossy().to_string(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preset(name: &str) -> GrainPreset {
+        let params = serde_json::from_str(concat!(
+            r#"{"film_stock":"Kodak Portra 400","exposure_compensation":0,"size_multiplier":1,"contrast":100,"#,
+            r#""grain_density":1000,"width":640,"height":480,"background":"transparent"}"#
+        ))
+        .unwrap();
+        GrainPreset { format_version: PRESET_FORMAT_VERSION, name: name.to_string(), description: String::new(), params }
+    }
+
+    #[test]
+    fn names_sharing_a_file_never_replace_each_other() {
+        let dir = std::env::temp_dir().join(format!("fgp-presets-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let path = write_preset_in(&dir, &preset("Portra 400")).unwrap();
+        assert_eq!(path, dir.join("Portra_400.json"));
+        for other in ["Portra_400", "Portra/400"] {
+            assert!(write_preset_in(&dir, &preset(other)).unwrap_err().contains("Portra 400"));
+            assert!(find_preset_in(&dir, other).is_err());
+        }
+        assert_eq!(find_preset_in(&dir, "Portra 400").unwrap().name, "Portra 400");
+
+        // The same preset saves over itself
+        let mut updated = preset("Portra 400");
+        updated.description = "Warmer".to_string();
+        write_preset_in(&dir, &updated).unwrap();
+        assert_eq!(find_preset_in(&dir, "Portra 400").unwrap().description, "Warmer");
+    }
+}
@@ -0,0 +1,682 @@
+use rayon::prelude::*;
+use std::ops::RangeInclusive;
+use wide::*;
+
+use crate::development::Development;
+use crate::dye_layers;
+use crate::motion_effects;
+use crate::{
+    get_coupler_spread, get_crystal_shape, get_json_contrast_multiplier, get_json_edge_softness, grain_hash_unit, render_bw_film_grain, render_color_film_grain, CrystalShape,
+    FilmStock, Grain, GrainParams,
+};
+
+// Grains are binned into square tiles; each tile is rasterised by one rayon task straight
+// into its own rows of the output buffer, so no pixel lists are collected or merged.
+pub const RASTER_TILE_SIZE: usize = 128;
+const LANES: usize = 8;
+// A single grain never blocks all light, so its density stays finite
+const MAX_PIXEL_ALPHA: f32 = 254.0;
+// Fixed-point steps per unit of optical density
+const DENSITY_SCALE: f32 = 1024.0;
+
+// Crystal outlines are sized to the same area as the ellipse they replace, so switching
+// shape changes the texture and not how much the grain darkens
+const CUBIC_HALF_SIDE: f32 = 0.886; // √π / 2
+const CUBIC_CORNER_RADIUS: f32 = 0.15;
+const TABULAR_INRADIUS: f32 = 0.952; // √(π / 2√3)
+// Largest bump of an irregular outline, as a fraction of the radius
+const IRREGULARITY: f32 = 0.18;
+// Dye clouds are cut off this many standard deviations out, where 2% of the peak remains
+const CLOUD_REACH: f32 = 2.8;
+
+// Everything about a grain the rasteriser needs, worked out once per grain. Positions are
+// continuous canvas coordinates; pixel (x, y) covers [x, x+1) × [y, y+1).
+#[derive(Debug, Clone, Copy)]
+pub struct GrainStamp {
+    center_x: f32,
+    center_y: f32,
+    // Canvas pixels the grain can touch, inclusive
+    min_x: i32,
+    max_x: i32,
+    min_y: i32,
+    max_y: i32,
+    footprint: Footprint,
+    color: [i32; 3],
+    alpha: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Footprint {
+    Crystal(CrystalOutline),
+    Cloud(DyeCloud),
+    // Smaller than a pixel: its area, shared bilinearly between the four nearest pixels so
+    // it neither vanishes nor snaps to one whole pixel
+    Splat { area: f32 },
+}
+
+// Crystal outline of radius edge_mid, given as a signed distance function. Its edge fades
+// out linearly over at least one pixel, so it's anti-aliased instead of stepping between
+// pixel centres.
+#[derive(Debug, Clone, Copy)]
+struct CrystalOutline {
+    crystal: CrystalShape,
+    edge_mid: f32,
+    edge_width_inv: f32,
+    axes: GrainAxes,
+    harmonics: [f32; 4], // Irregular outlines: cos/sin weights of the 2nd and 3rd harmonics
+}
+
+// Soft dye cloud a chromogenic grain leaves behind: a Gaussian of standard deviation
+// 1/sigma_inv with a lumpy outline, and no edge at all
+#[derive(Debug, Clone, Copy)]
+struct DyeCloud {
+    sigma_inv: f32,
+    axes: GrainAxes,
+    harmonics: [f32; 4],
+}
+
+// Maps canvas offsets into a grain's own axes: rotated by its angle, stretch undone
+#[derive(Debug, Clone, Copy)]
+struct GrainAxes {
+    shape_factor_inv: f32,
+    cos_angle: f32,
+    sin_angle: f32,
+}
+
+impl GrainStamp {
+    // `edge_softness` is the stock's edge type as developed, worked out once per render
+    pub fn new(grain: &Grain, stock: &FilmStock, params: &GrainParams, edge_softness: f32) -> Self {
+        // Enhanced color film simulation with multi-layer rendering
+        let (mut r, mut g, mut b) = if stock.basic_info.film_type == "color" {
+            render_color_film_grain(grain, stock, params)
+        } else {
+            render_bw_film_grain(grain, stock, params)
+        };
+
+        // Individual grain color variation for color films
+        if stock.basic_info.film_type == "color" {
+            let color_var = 0.92 + grain_hash_unit(grain, motion_effects::grain_seed(params)) * 0.16;
+            r = (r as f32 * color_var).clamp(0.0, 255.0) as u8;
+            g = (g as f32 * color_var).clamp(0.0, 255.0) as u8;
+            b = (b as f32 * color_var).clamp(0.0, 255.0) as u8;
+        }
+
+        // Dye clouds of each colour layer hold back the channel that layer records
+        if let Some(layer) = grain.dye_layer {
+            let tint = dye_layers::layer_tint(layer);
+            r = (r as f32 * tint[0]).round() as u8;
+            g = (g as f32 * tint[1]).round() as u8;
+            b = (b as f32 * tint[2]).round() as u8;
+        }
+
+        let contrast_multiplier = get_json_contrast_multiplier(&stock.visual_properties.contrast_level);
+        let alpha = (grain.opacity * 255.0 * 2.0 * contrast_multiplier).clamp(40.0, 255.0);
+
+        // Edge falloff from the JSON edge_type, sharpened or softened by the developer: a ramp
+        // ending at the grain's size
+        let edge_width = match stock.grain_structure.edge_type.as_str() {
+            "soft" => grain.size * 0.4 * edge_softness,
+            "hard" => 0.0,
+            _ => grain.size * 0.15 * edge_softness,
+        };
+        let edge_mid = match stock.grain_structure.edge_type.as_str() {
+            "hard" => grain.size * 0.98,
+            _ => grain.size - edge_width * 0.5,
+        };
+
+        // Chromogenic stocks show the dye their couplers formed around each grain, never the
+        // grain itself, so the crystal outline and edge type don't apply
+        let color = [r as i32, g as i32, b as i32];
+        let seed = motion_effects::grain_seed(params);
+        if let Some(coupler_spread) = get_coupler_spread(&stock.basic_info.process) {
+            return Self::clouded(grain, coupler_spread, grain_lumps(grain, seed), color, alpha);
+        }
+
+        // Each irregular grain gets its own lumps, stable for the seed
+        let crystal = get_crystal_shape(&stock.grain_structure);
+        let harmonics = match crystal {
+            CrystalShape::Irregular => grain_lumps(grain, seed),
+            _ => [0.0; 4],
+        };
+
+        Self::shaped(grain, crystal, harmonics, (edge_mid, edge_width), color, alpha)
+    }
+
+    // Footprint for the grain's position, stretch and rotation with the given crystal
+    // outline and (midpoint, width) edge ramp
+    fn shaped(grain: &Grain, crystal: CrystalShape, harmonics: [f32; 4], (edge_mid, edge_width): (f32, f32), color: [i32; 3], alpha: f32) -> Self {
+        let edge_mid = edge_mid.max(0.0);
+        let area = std::f32::consts::PI * edge_mid * edge_mid * grain.shape_factor;
+        if area < 1.0 {
+            return Self::splat(grain, area, color, alpha);
+        }
+
+        // Widening a ramp about its midpoint keeps the grain's weight the same
+        let edge_width = edge_width.max(1.0);
+        let outline_reach = match crystal {
+            CrystalShape::Rounded => 1.0,
+            CrystalShape::Irregular => 1.0 + IRREGULARITY,
+            CrystalShape::Cubic => (CUBIC_HALF_SIDE - CUBIC_CORNER_RADIUS) * std::f32::consts::SQRT_2 + CUBIC_CORNER_RADIUS,
+            CrystalShape::Tabular => TABULAR_INRADIUS * 2.0 / 3.0f32.sqrt(),
+        };
+        let reach = edge_mid * outline_reach + edge_width * 0.5;
+        let footprint = Footprint::Crystal(CrystalOutline { crystal, edge_mid, edge_width_inv: 1.0 / edge_width, axes: GrainAxes::of(grain), harmonics });
+        Self::framed(grain, reach, footprint, color, alpha)
+    }
+
+    // Dye cloud for the grain. Coupler spread widens it by that factor and lowers its peak
+    // to match, so it holds the same dye the crystal would: a Gaussian of peak 1 covers
+    // 2πσ², a disc πr².
+    fn clouded(grain: &Grain, coupler_spread: f32, harmonics: [f32; 4], color: [i32; 3], alpha: f32) -> Self {
+        let area = std::f32::consts::PI * grain.size * grain.size * grain.shape_factor;
+        if area < 1.0 {
+            return Self::splat(grain, area, color, alpha);
+        }
+
+        // Clouds no narrower than a pixel, so they're sampled smoothly
+        let sigma = (grain.size * coupler_spread * std::f32::consts::FRAC_1_SQRT_2).max(1.0);
+        let peak = alpha * grain.size * grain.size / (2.0 * sigma * sigma);
+        let reach = sigma * CLOUD_REACH * (1.0 + IRREGULARITY);
+        let footprint = Footprint::Cloud(DyeCloud { sigma_inv: 1.0 / sigma, axes: GrainAxes::of(grain), harmonics });
+        Self::framed(grain, reach, footprint, color, peak)
+    }
+
+    fn splat(grain: &Grain, area: f32, color: [i32; 3], alpha: f32) -> Self {
+        let (left, top) = ((grain.x - 0.5).floor() as i32, (grain.y - 0.5).floor() as i32);
+        Self {
+            center_x: grain.x,
+            center_y: grain.y,
+            min_x: left,
+            max_x: left + 1,
+            min_y: top,
+            max_y: top + 1,
+            footprint: Footprint::Splat { area },
+            color,
+            alpha,
+        }
+    }
+
+    // Stamp bounded by the rotated ellipse `reach` pixels out along the grain's short axis
+    fn framed(grain: &Grain, reach: f32, footprint: Footprint, color: [i32; 3], alpha: f32) -> Self {
+        let (x, y) = (grain.x, grain.y);
+        let (sin_angle, cos_angle) = grain.angle.sin_cos();
+        let (along, across) = (reach * grain.shape_factor, reach);
+        let extent_x = (along * cos_angle).hypot(across * sin_angle);
+        let extent_y = (along * sin_angle).hypot(across * cos_angle);
+        Self {
+            center_x: x,
+            center_y: y,
+            min_x: (x - extent_x).floor() as i32,
+            max_x: (x + extent_x).floor() as i32,
+            min_y: (y - extent_y).floor() as i32,
+            max_y: (y + extent_y).floor() as i32,
+            footprint,
+            color,
+            alpha,
+        }
+    }
+}
+
+// Per-grain lumps for irregular outlines and dye clouds, stable for the seed
+fn grain_lumps(grain: &Grain, seed: u64) -> [f32; 4] {
+    let phase = |salt: u64| grain_hash_unit(grain, seed ^ salt) * std::f32::consts::TAU;
+    let (second, third) = (phase(0x5eed_0002), phase(0x5eed_0003));
+    let (strength_2, strength_3) = (IRREGULARITY * 0.6, IRREGULARITY * 0.4);
+    [strength_2 * second.cos(), strength_2 * second.sin(), strength_3 * third.cos(), strength_3 * third.sin()]
+}
+
+// Distance from the centre and the outline's relative bump in that direction, from the
+// 2nd and 3rd harmonics via double-angle identities
+#[inline(always)]
+fn harmonic_bump(harmonics: &[f32; 4], x: f32x8, y: f32x8) -> (f32x8, f32x8) {
+    let length = (x * x + y * y).sqrt();
+    let inv_length = f32x8::ONE / length.max(f32x8::splat(1e-6));
+    let (cos_1, sin_1) = (x * inv_length, y * inv_length);
+    let (cos_2, sin_2) = (cos_1 * cos_1 - sin_1 * sin_1, f32x8::splat(2.0) * cos_1 * sin_1);
+    let (cos_3, sin_3) = (cos_1 * cos_2 - sin_1 * sin_2, sin_1 * cos_2 + cos_1 * sin_2);
+    let [a2, b2, a3, b3] = harmonics.map(f32x8::splat);
+    (length, a2 * cos_2 + b2 * sin_2 + a3 * cos_3 + b3 * sin_3)
+}
+
+impl GrainAxes {
+    fn of(grain: &Grain) -> Self {
+        let (sin_angle, cos_angle) = grain.angle.sin_cos();
+        Self { shape_factor_inv: 1.0 / grain.shape_factor, cos_angle, sin_angle }
+    }
+}
+
+impl CrystalOutline {
+    // Signed distances in pixels from the outline (negative inside) for points in the
+    // grain's own unstretched axes, one per crystal shape
+    #[inline(always)]
+    fn rounded(&self, x: f32x8, y: f32x8) -> f32x8 {
+        (x * x + y * y).sqrt() - f32x8::splat(self.edge_mid)
+    }
+
+    #[inline(always)]
+    fn irregular(&self, x: f32x8, y: f32x8) -> f32x8 {
+        // Radius bumped by two harmonics of the direction
+        let (length, bump) = harmonic_bump(&self.harmonics, x, y);
+        length - f32x8::splat(self.edge_mid) * (f32x8::ONE + bump)
+    }
+
+    #[inline(always)]
+    fn cubic(&self, x: f32x8, y: f32x8) -> f32x8 {
+        let zero = f32x8::ZERO;
+        let corner = f32x8::splat(self.edge_mid * CUBIC_CORNER_RADIUS);
+        let half_side = f32x8::splat(self.edge_mid * CUBIC_HALF_SIDE) - corner;
+        let (qx, qy) = (x.abs() - half_side, y.abs() - half_side);
+        let (ox, oy) = (qx.max(zero), qy.max(zero));
+        (ox * ox + oy * oy).sqrt() + qx.max(qy).min(zero) - corner
+    }
+
+    #[inline(always)]
+    fn tabular(&self, x: f32x8, y: f32x8) -> f32x8 {
+        // Regular hexagon: fold into one sextant, then measure to its flat edge
+        let zero = f32x8::ZERO;
+        let inradius = self.edge_mid * TABULAR_INRADIUS;
+        let (kx, ky) = (f32x8::splat(-0.866_025_4), f32x8::splat(0.5));
+        let (px, py) = (x.abs(), y.abs());
+        let fold = (kx * px + ky * py).min(zero) * f32x8::splat(2.0);
+        let (px, py) = (px - fold * kx, py - fold * ky);
+        let half_edge = f32x8::splat(inradius * 0.577_350_3);
+        let (dx, dy) = (px - px.max(-half_edge).min(half_edge), py - f32x8::splat(inradius));
+        let length = (dx * dx + dy * dy).sqrt();
+        dy.cmp_lt(zero).blend(-length, length)
+    }
+}
+
+// Per-pixel sums for one tile, rows padded to whole SIMD lanes. Each rayon worker reuses
+// one across the tiles it renders.
+//
+// Grains don't blend over each other: every covered pixel adds the grain's optical density
+// (-ln of the light it lets through) and its density-weighted colour, in fixed point. Integer
+// sums don't depend on order, so the result is the same whatever order tiles, threads or
+// grains run in. Density maps back to alpha as 1 - e^-D, which leaves a lone grain exactly
+// as it was and makes overlaps as opaque as stacking them would.
+struct TileCanvas {
+    stride: usize,
+    density: Vec<i32>,
+    // Density × colour: wide enough for thousands of overlapping opaque grains
+    weighted_color: [Vec<i64>; 3],
+    density_of_alpha: [i32; 256], // Fixed-point density for each 8-bit alpha
+}
+
+impl TileCanvas {
+    fn new() -> Self {
+        let stride = RASTER_TILE_SIZE.div_ceil(LANES) * LANES;
+        let len = stride * RASTER_TILE_SIZE;
+        Self {
+            stride,
+            density: vec![0; len],
+            weighted_color: std::array::from_fn(|_| vec![0; len]),
+            density_of_alpha: std::array::from_fn(|alpha| (alpha_to_density(alpha as f32) * DENSITY_SCALE).round() as i32),
+        }
+    }
+
+    fn clear(&mut self, height: usize) {
+        let used = self.stride * height;
+        self.density[..used].fill(0);
+        for plane in &mut self.weighted_color {
+            plane[..used].fill(0);
+        }
+    }
+
+    #[inline(always)]
+    fn accumulate(plane: &mut [i32], at: usize, value: i32x8) {
+        let sum = i32x8::new(plane[at..at + LANES].try_into().unwrap()) + value;
+        plane[at..at + LANES].copy_from_slice(&sum.to_array());
+    }
+
+    // Adds density × colour for eight pixels, widening to 64 bits
+    #[inline(always)]
+    fn accumulate_weighted(plane: &mut [i64], at: usize, density: i32x8, value: i32x4) {
+        for (half, lanes) in density.to_array().chunks_exact(4).enumerate() {
+            let at = at + half * 4;
+            let weighted = i32x4::new(lanes.try_into().unwrap()).mul_widen(value);
+            let sum = i64x4::new(plane[at..at + 4].try_into().unwrap()) + weighted;
+            plane[at..at + 4].copy_from_slice(&sum.to_array());
+        }
+    }
+
+    // Adds a pixel's share of a grain's density and colour
+    #[inline(always)]
+    fn add(&mut self, at: usize, alpha: f32, color: [i32; 3]) {
+        let density = self.density_of_alpha[alpha.round().min(255.0) as usize];
+        self.density[at] += density;
+        for (plane, value) in self.weighted_color.iter_mut().zip(color) {
+            plane[at] += density as i64 * value as i64;
+        }
+    }
+
+    // Adds one stamp's density to the pixels it covers. `left`/`top` is the tile's canvas
+    // position and `width`/`height` its size.
+    fn draw(&mut self, stamp: &GrainStamp, left: i32, top: i32, width: i32, height: i32) {
+        let first_x = (stamp.min_x - left).max(0);
+        let last_x = (stamp.max_x - left).min(width - 1);
+        let first_y = (stamp.min_y - top).max(0);
+        let last_y = (stamp.max_y - top).min(height - 1);
+        if first_x > last_x || first_y > last_y {
+            return;
+        }
+
+        match stamp.footprint {
+            Footprint::Splat { area } => {
+                let fx = stamp.center_x - 0.5 - stamp.min_x as f32;
+                let fy = stamp.center_y - 0.5 - stamp.min_y as f32;
+                for (dx, dy, weight) in [(0, 0, (1.0 - fx) * (1.0 - fy)), (1, 0, fx * (1.0 - fy)), (0, 1, (1.0 - fx) * fy), (1, 1, fx * fy)] {
+                    let (x, y) = (stamp.min_x + dx - left, stamp.min_y + dy - top);
+                    if x >= first_x && x <= last_x && y >= first_y && y <= last_y {
+                        self.add(y as usize * self.stride + x as usize, stamp.alpha * area * weight, stamp.color);
+                    }
+                }
+            }
+            Footprint::Crystal(outline) => self.draw_crystal(stamp, outline, (left, top), first_x..=last_x, first_y..=last_y),
+            Footprint::Cloud(cloud) => self.draw_cloud(stamp, cloud, (left, top), first_x..=last_x, first_y..=last_y),
+        }
+    }
+
+    // Picks the outline once per grain so the pixel loop is specialised for its shape
+    fn draw_crystal(&mut self, stamp: &GrainStamp, outline: CrystalOutline, origin: (i32, i32), xs: RangeInclusive<i32>, ys: RangeInclusive<i32>) {
+        match outline.crystal {
+            CrystalShape::Rounded => self.fill_outline(stamp, &outline, origin, xs, ys, CrystalOutline::rounded),
+            CrystalShape::Irregular => self.fill_outline(stamp, &outline, origin, xs, ys, CrystalOutline::irregular),
+            CrystalShape::Cubic => self.fill_outline(stamp, &outline, origin, xs, ys, CrystalOutline::cubic),
+            CrystalShape::Tabular => self.fill_outline(stamp, &outline, origin, xs, ys, CrystalOutline::tabular),
+        }
+    }
+
+    // Crystal coverage: the outline's edge ramp
+    #[inline(always)]
+    fn fill_outline(
+        &mut self,
+        stamp: &GrainStamp,
+        outline: &CrystalOutline,
+        origin: (i32, i32),
+        xs: RangeInclusive<i32>,
+        ys: RangeInclusive<i32>,
+        signed_distance: impl Fn(&CrystalOutline, f32x8, f32x8) -> f32x8,
+    ) {
+        let edge_width_inv = f32x8::splat(outline.edge_width_inv);
+        let half = f32x8::splat(0.5);
+        self.fill(stamp, &outline.axes, origin, xs, ys, |x, y| (half - signed_distance(outline, x, y) * edge_width_inv).max(f32x8::ZERO).min(f32x8::ONE));
+    }
+
+    // Cloud coverage: the Gaussian, stretched out where the cloud's outline bulges
+    fn draw_cloud(&mut self, stamp: &GrainStamp, cloud: DyeCloud, origin: (i32, i32), xs: RangeInclusive<i32>, ys: RangeInclusive<i32>) {
+        let sigma_inv = f32x8::splat(cloud.sigma_inv);
+        let minus_half = f32x8::splat(-0.5);
+        self.fill(stamp, &cloud.axes, origin, xs, ys, |x, y| {
+            let (length, bump) = harmonic_bump(&cloud.harmonics, x, y);
+            let deviations = length * sigma_inv / (f32x8::ONE + bump);
+            (minus_half * deviations * deviations).exp()
+        });
+    }
+
+    // Adds the stamp's coverage at pixel centres, eight pixels at a time. `coverage` takes
+    // offsets in the grain's own axes and gives 0 to 1.
+    #[inline(always)]
+    fn fill(
+        &mut self,
+        stamp: &GrainStamp,
+        axes: &GrainAxes,
+        (left, top): (i32, i32),
+        xs: RangeInclusive<i32>,
+        ys: RangeInclusive<i32>,
+        coverage: impl Fn(f32x8, f32x8) -> f32x8,
+    ) {
+        let (first_x, last_x) = (*xs.start(), *xs.end());
+        let lane_offsets = f32x8::new([0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0]);
+        let span_start = f32x8::splat(first_x as f32);
+        let span_end = f32x8::splat(last_x as f32);
+        let shape_factor_inv = f32x8::splat(axes.shape_factor_inv);
+        let (cos_angle, sin_angle) = (f32x8::splat(axes.cos_angle), f32x8::splat(axes.sin_angle));
+        let to_center = f32x8::splat(left as f32 + 0.5 - stamp.center_x);
+        let grain_alpha = f32x8::splat(stamp.alpha);
+        let color = stamp.color.map(i32x4::splat);
+        let zero = f32x8::ZERO;
+
+        for y in ys {
+            let dy = f32x8::splat((top + y) as f32 + 0.5 - stamp.center_y);
+            let row = y as usize * self.stride;
+
+            let mut x = first_x as usize / LANES * LANES;
+            while x <= last_x as usize {
+                let lane_x = f32x8::splat(x as f32) + lane_offsets;
+                let dx = lane_x + to_center;
+                // Into the grain's own axes, then undo its stretch
+                let along = (dx * cos_angle + dy * sin_angle) * shape_factor_inv;
+                let across = dy * cos_angle - dx * sin_angle;
+                let coverage = coverage(along, across);
+                let visible = lane_x.cmp_ge(span_start) & lane_x.cmp_le(span_end) & coverage.cmp_gt(zero);
+                if visible.any() {
+                    let at = row + x;
+                    let alpha = visible.blend(grain_alpha * coverage, zero).round_int().to_array();
+                    let density = i32x8::new(alpha.map(|alpha| self.density_of_alpha[alpha.min(255) as usize]));
+                    Self::accumulate(&mut self.density, at, density);
+                    for (plane, value) in self.weighted_color.iter_mut().zip(color) {
+                        Self::accumulate_weighted(plane, at, density, value);
+                    }
+                }
+                x += LANES;
+            }
+        }
+    }
+
+    // Maps the tile's sums to RGBA8 in its rows of the output buffer. Colour stays
+    // premultiplied by coverage, as the over-blended layer on transparent black was.
+    fn write_rows(&self, rows: Vec<&mut [u8]>) {
+        let [red, green, blue] = &self.weighted_color;
+        for (y, row) in rows.into_iter().enumerate() {
+            let start = y * self.stride;
+            let end = start + row.len() / 4;
+            let sums = self.density[start..end].iter().zip(&red[start..end]).zip(&green[start..end]).zip(&blue[start..end]);
+            for (pixel, (((density, r), g), b)) in row.chunks_exact_mut(4).zip(sums) {
+                if *density == 0 {
+                    pixel.fill(0);
+                    continue;
+                }
+                let coverage = 1.0 - (-(*density as f32) / DENSITY_SCALE).exp();
+                let scale = coverage / *density as f32;
+                pixel.copy_from_slice(&[
+                    (*r as f32 * scale) as u8,
+                    (*g as f32 * scale) as u8,
+                    (*b as f32 * scale) as u8,
+                    (coverage * 255.0) as u8,
+                ]);
+            }
+        }
+    }
+}
+
+// Optical density of a pixel covered at `alpha` (0-255); 0 for uncovered pixels
+fn alpha_to_density(alpha: f32) -> f32 {
+    -(1.0 - alpha.min(MAX_PIXEL_ALPHA) / 255.0).ln()
+}
+
+// Rasterises stamps into `out`, a width×height RGBA8 region whose top-left pixel sits at
+// (left, top) on the canvas. The order of the stamps doesn't affect the result.
+pub fn rasterise<'a, I>(stamps: I, left: u32, top: u32, width: u32, height: u32, out: &mut [u8])
+where
+    I: IntoIterator<Item = &'a GrainStamp>,
+{
+    let (width, height) = (width as usize, height as usize);
+    let columns = width.div_ceil(RASTER_TILE_SIZE);
+    let rows = height.div_ceil(RASTER_TILE_SIZE);
+    if columns == 0 || rows == 0 {
+        return;
+    }
+
+    // Bin each stamp into every tile its bounding box touches
+    let mut bins: Vec<Vec<&GrainStamp>> = vec![Vec::new(); columns * rows];
+    for stamp in stamps {
+        let min_x = stamp.min_x - left as i32;
+        let max_x = stamp.max_x - left as i32;
+        let min_y = stamp.min_y - top as i32;
+        let max_y = stamp.max_y - top as i32;
+        if max_x < 0 || max_y < 0 || min_x >= width as i32 || min_y >= height as i32 {
+            continue;
+        }
+        let tile_range = |min: i32, max: i32, count: usize| {
+            (min.max(0) as usize / RASTER_TILE_SIZE)..=(max as usize / RASTER_TILE_SIZE).min(count - 1)
+        };
+        for ty in tile_range(min_y, max_y, rows) {
+            for tx in tile_range(min_x, max_x, columns) {
+                bins[ty * columns + tx].push(stamp);
+            }
+        }
+    }
+
+    // Hand each tile its own slices of the output rows
+    let mut tile_rows: Vec<Vec<&mut [u8]>> = (0..columns * rows).map(|_| Vec::new()).collect();
+    for (y, row) in out.chunks_exact_mut(width * 4).enumerate() {
+        let ty = y / RASTER_TILE_SIZE;
+        for (tx, piece) in row.chunks_mut(RASTER_TILE_SIZE * 4).enumerate() {
+            tile_rows[ty * columns + tx].push(piece);
+        }
+    }
+
+    tile_rows.into_par_iter().zip(bins.par_iter()).enumerate().for_each_init(TileCanvas::new, |canvas, (index, (rows, stamps))| {
+        let (tx, ty) = (index % columns, index / columns);
+        let tile_width = RASTER_TILE_SIZE.min(width - tx * RASTER_TILE_SIZE);
+        let tile_height = rows.len();
+        canvas.clear(tile_height);
+        let tile_left = (left as usize + tx * RASTER_TILE_SIZE) as i32;
+        let tile_top = (top as usize + ty * RASTER_TILE_SIZE) as i32;
+        for stamp in stamps {
+            canvas.draw(stamp, tile_left, tile_top, tile_width as i32, tile_height as i32);
+        }
+        canvas.write_rows(rows);
+    });
+}
+
+// Stamps for a list of grains, worked out in parallel
+pub fn grain_stamps(grains: &[Grain], stock: &FilmStock, params: &GrainParams) -> Vec<GrainStamp> {
+    let edge_softness = get_json_edge_softness(&stock.grain_structure.edge_type) * Development::for_params(params, stock).edge_softness;
+    grains.par_iter().map(|grain| GrainStamp::new(grain, stock, params, edge_softness)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stamp(x: f32, y: f32, size: f32, color: [i32; 3], alpha: f32) -> GrainStamp {
+        oriented_stamp(x, y, size, 1.0, 0.0, color, alpha)
+    }
+
+    fn oriented_stamp(x: f32, y: f32, size: f32, shape_factor: f32, angle: f32, color: [i32; 3], alpha: f32) -> GrainStamp {
+        crystal_stamp(CrystalShape::Rounded, [0.0; 4], Grain { x, y, size, opacity: 1.0, shape_factor, angle, dye_layer: None }, color, alpha)
+    }
+
+    fn crystal_stamp(crystal: CrystalShape, harmonics: [f32; 4], grain: Grain, color: [i32; 3], alpha: f32) -> GrainStamp {
+        let edge_width = grain.size * 0.15;
+        GrainStamp::shaped(&grain, crystal, harmonics, (grain.size - edge_width * 0.5, edge_width), color, alpha)
+    }
+
+    fn render(stamps: &[GrainStamp], width: u32, height: u32) -> Vec<u8> {
+        let mut out = vec![0; width as usize * height as usize * 4];
+        rasterise(stamps, 0, 0, width, height, &mut out);
+        out
+    }
+
+    fn total_alpha(out: &[u8]) -> u32 {
+        out.chunks_exact(4).map(|pixel| pixel[3] as u32).sum()
+    }
+
+    #[test]
+    fn blending_does_not_depend_on_grain_order() {
+        // Overlapping grains of different colours, some straddling tile edges
+        let mut stamps: Vec<GrainStamp> = (0..400)
+            .map(|i| {
+                let (x, y) = ((i * 37 % 300) as f32 + 0.1 * (i % 10) as f32, (i * 53 % 200) as f32 + 0.37);
+                stamp(x, y, (i % 7) as f32 * 1.5 + 0.3, [i * 13 % 256, i * 29 % 256, i * 71 % 256], (60 + i % 190) as f32)
+            })
+            .collect();
+
+        let forward = render(&stamps, 300, 200);
+        stamps.reverse();
+        assert_eq!(render(&stamps, 300, 200), forward);
+        stamps.rotate_left(123);
+        assert_eq!(render(&stamps, 300, 200), forward);
+    }
+
+    #[test]
+    fn thousands_of_coincident_grains_keep_their_colour() {
+        // Far more density × colour than fits in 32 bits, through both the crystal and splat paths
+        for size in [3.0, 0.4] {
+            let stamps = vec![stamp(4.5, 4.5, size, [255, 128, 0], 250.0); 4000];
+            let out = render(&stamps, 10, 10);
+            assert_eq!(&out[(4 * 10 + 4) * 4..][..4], &[255, 128, 0, 255], "size {}", size);
+        }
+    }
+
+    #[test]
+    fn grain_covering_a_pixel_keeps_its_colour_and_alpha() {
+        let out = render(&[stamp(4.5, 4.5, 3.0, [200, 100, 50], 128.0)], 10, 10);
+        let pixel = &out[(4 * 10 + 4) * 4..][..4];
+        assert_eq!(pixel[3], 128);
+        // Colour is premultiplied by coverage
+        assert!((pixel[0] as i32 - 100).abs() <= 1 && (pixel[1] as i32 - 50).abs() <= 1 && (pixel[2] as i32 - 25).abs() <= 1);
+    }
+
+    #[test]
+    fn sub_pixel_grains_keep_their_weight_wherever_they_land() {
+        let centred = total_alpha(&render(&[stamp(4.5, 4.5, 0.4, [255, 255, 255], 200.0)], 10, 10));
+        assert!(centred > 0, "sub-pixel grain vanished");
+        assert!(centred < 200, "sub-pixel grain drawn as a whole pixel");
+        for (x, y) in [(4.0, 4.0), (4.25, 4.8), (4.9, 4.1)] {
+            let shifted = total_alpha(&render(&[stamp(x, y, 0.4, [255, 255, 255], 200.0)], 10, 10));
+            assert!(shifted.abs_diff(centred) <= 4, "{} at ({}, {}) vs {} centred", shifted, x, y, centred);
+        }
+    }
+
+    #[test]
+    fn elongated_grains_follow_their_angle() {
+        let covered = |angle: f32| {
+            let out = render(&[oriented_stamp(20.0, 20.0, 4.0, 3.0, angle, [255, 255, 255], 255.0)], 40, 40);
+            let alpha = |x: usize, y: usize| out[(y * 40 + x) * 4 + 3];
+            (alpha(29, 20), alpha(20, 29))
+        };
+        // Stretched along x unrotated, along y after a quarter turn
+        let (along_x, along_y) = covered(0.0);
+        assert!(along_x > 200 && along_y == 0);
+        let (along_x, along_y) = covered(std::f32::consts::FRAC_PI_2);
+        assert!(along_x == 0 && along_y > 200);
+    }
+
+    #[test]
+    fn crystal_outlines_keep_the_grain_weight() {
+        let grain = Grain { x: 30.0, y: 30.0, size: 12.0, opacity: 1.0, shape_factor: 1.0, angle: 0.3, dye_layer: None };
+        let weight = |crystal, harmonics| {
+            let out = render(&[crystal_stamp(crystal, harmonics, grain, [255, 255, 255], 100.0)], 60, 60);
+            (total_alpha(&out), out)
+        };
+        let (rounded, rounded_out) = weight(CrystalShape::Rounded, [0.0; 4]);
+        let (cubic, cubic_out) = weight(CrystalShape::Cubic, [0.0; 4]);
+        let (tabular, _) = weight(CrystalShape::Tabular, [0.0; 4]);
+        let (irregular, _) = weight(CrystalShape::Irregular, [0.1, -0.05, 0.04, 0.06]);
+        for other in [cubic, tabular, irregular] {
+            assert!(other.abs_diff(rounded) * 100 < rounded * 4, "{} vs {} for a round grain", other, rounded);
+        }
+
+        // A cube reaches into the corners a disc of the same area leaves empty
+        let (sin, cos) = (0.3f32 + std::f32::consts::FRAC_PI_4).sin_cos();
+        let at = ((30.0 + 13.5 * sin) as usize * 60 + (30.0 + 13.5 * cos) as usize) * 4 + 3;
+        assert!(cubic_out[at] > 0 && rounded_out[at] == 0);
+    }
+
+    #[test]
+    fn dye_clouds_spread_the_grain_weight() {
+        // Faint enough that density adds up linearly
+        let grain = Grain { x: 40.0, y: 40.0, size: 6.0, opacity: 1.0, shape_factor: 1.0, angle: 0.0, dye_layer: None };
+        let crystal = render(&[crystal_stamp(CrystalShape::Rounded, [0.0; 4], grain, [255, 255, 255], 30.0)], 80, 80);
+        let cloud = render(&[GrainStamp::clouded(&grain, 1.5, [0.0; 4], [255, 255, 255], 30.0)], 80, 80);
+
+        let (crystal_weight, cloud_weight) = (total_alpha(&crystal) as f32, total_alpha(&cloud) as f32);
+        assert!((cloud_weight / crystal_weight - 1.0).abs() < 0.1, "{} vs {}", cloud_weight, crystal_weight);
+        let peak = |out: &[u8]| out.chunks_exact(4).map(|pixel| pixel[3]).max().unwrap();
+        let covered = |out: &[u8]| out.chunks_exact(4).filter(|pixel| pixel[3] > 0).count();
+        assert!(peak(&cloud) < peak(&crystal));
+        assert!(covered(&cloud) > covered(&crystal));
+    }
+}
@@ -0,0 +1,66 @@
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::{ExtendedColorType, ImageEncoder, RgbaImage};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::GrainParams;
+
+// Renders stay in the backend and the webview only holds their ID, so full RGBA buffers
+// never cross IPC as JSON. A 4K canvas is 64 MB, so only the most recent few are kept.
+const MAX_CACHED_RENDERS: usize = 4;
+
+#[derive(Clone)]
+pub struct CachedRender {
+    pub image: Arc<RgbaImage>,
+    pub params: GrainParams, // Exactly what was rendered, seed included
+}
+
+#[derive(Default)]
+pub struct RenderCache {
+    next_id: AtomicU64,
+    renders: Mutex<VecDeque<(u64, CachedRender)>>,
+}
+
+impl RenderCache {
+    pub fn insert(&self, image: RgbaImage, params: GrainParams) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut renders = self.renders.lock().unwrap_or_else(|e| e.into_inner());
+
+        renders.push_back((id, CachedRender { image: Arc::new(image), params }));
+        while renders.len() > MAX_CACHED_RENDERS {
+            renders.pop_front();
+        }
+        id
+    }
+
+    // Looking a render up marks it as recently used
+    pub fn get(&self, id: u64) -> Result<CachedRender, String> {
+        let mut renders = self.renders.lock().unwrap_or_else(|e| e.into_inner());
+        let index = renders
+            .iter()
+            .position(|(render_id, _)| *render_id == id)
+            .ok_or_else(|| format!("Render {} is no longer available - regenerate the grain", id))?;
+
+        let entry = renders.remove(index).expect("index from position");
+        let render = entry.1.clone();
+        renders.push_back(entry);
+        Ok(render)
+    }
+}
+
+// Fast PNG: the preview is decoded straight away by the webview, so speed beats file size
+pub fn encode_preview(image: &RgbaImage) -> Result<Vec<u8>, String> {
+    let mut png = Vec::new();
+    PngEncoder::new_with_quality(&mut png, CompressionType::Fast, FilterType::Sub)
+        .write_image(image.as_raw(), image.width(), image.height(), ExtendedColorType::Rgba8)
+        .map_err(|e| format!("Failed to encode preview: {}", e))?;
+    Ok(png)
+}
+
+// Returned as a binary IPC response (an ArrayBuffer in the webview), not a JSON array
+#[tauri::command]
+pub async fn get_render_preview(cache: tauri::State<'_, RenderCache>, render_id: u64) -> Result<tauri::ipc::Response, String> {
+    let render = cache.get(render_id)?;
+    Ok(tauri::ipc::Response::new(encode_preview(&render.image)?))
+}
@@ -0,0 +1,190 @@
+use image::RgbaImage;
+use rayon::prelude::*;
+
+use crate::characteristic_curve::PhotoTones;
+use crate::raster::{self, GrainStamp};
+use crate::{boolean_model, film_format, generate_stock_grains, motion_effects, split_mix64, Grain, GrainParams, RenderMode, StockData};
+
+// Large canvases are rendered one band of tiles at a time. Each tile gets its own seeded
+// grain set, so any band can be generated without the rest of the canvas, and only three
+// bands of grains plus one band of pixels are ever held in memory.
+pub const TILE_SIZE: u32 = 512;
+// Above this the whole-canvas renderer's buffers get too large to hold comfortably
+pub const TILED_RENDER_MIN_PIXELS: u64 = 4096 * 4096;
+// Clustering thins and clamps grains near the edge of the area it generates in. Each tile
+// is generated this much larger on every side and keeps only the grains centred inside it,
+// so those edge effects fall in the discarded margin instead of showing as seams.
+const TILE_MARGIN: u32 = 64; // Wider than the 50px cluster radius
+
+pub fn should_tile(width: u32, height: u32) -> bool {
+    width as u64 * height as u64 > TILED_RENDER_MIN_PIXELS
+}
+
+struct TileGrid {
+    width: u32,
+    height: u32,
+    columns: u32,
+    rows: u32,
+}
+
+impl TileGrid {
+    fn new(width: u32, height: u32) -> Self {
+        Self { width, height, columns: width.div_ceil(TILE_SIZE), rows: height.div_ceil(TILE_SIZE) }
+    }
+
+    fn tile_width(&self, tx: u32) -> u32 {
+        TILE_SIZE.min(self.width - tx * TILE_SIZE)
+    }
+
+    fn tile_height(&self, ty: u32) -> u32 {
+        TILE_SIZE.min(self.height - ty * TILE_SIZE)
+    }
+}
+
+// Params for generating one tile's grains on its own: tile plus margin as the canvas, its own
+// seed (the frame's folded in), and the whole canvas's film scale pinned so sizes and density match an untiled render
+fn tile_params(params: &GrainParams, grid: &TileGrid, tx: u32, ty: u32) -> Result<GrainParams, String> {
+    let mut tile = params.clone();
+    if let Some(microns_per_pixel) = film_format::microns_per_pixel(params)? {
+        tile.scan_dpi = Some(film_format::dpi_for_microns_per_pixel(microns_per_pixel));
+    }
+    tile.width = grid.tile_width(tx) + 2 * TILE_MARGIN;
+    tile.height = grid.tile_height(ty) + 2 * TILE_MARGIN;
+    tile.seed = Some(split_mix64(motion_effects::grain_seed(params) ^ ((ty as u64) << 32 | tx as u64)));
+    tile.frame_index = None;
+    Ok(tile)
+}
+
+// All grains centred in one band of tiles, in canvas coordinates
+fn band_grains(data: &StockData, params: &GrainParams, grid: &TileGrid, ty: u32) -> Result<Vec<Grain>, String> {
+    let tiles: Vec<Vec<Grain>> = (0..grid.columns)
+        .into_par_iter()
+        .map(|tx| {
+            let (left, top) = (tx * TILE_SIZE, ty * TILE_SIZE);
+            let (right, bottom) = (left + grid.tile_width(tx), top + grid.tile_height(ty));
+            let grains = generate_stock_grains(data, &tile_params(params, grid, tx, ty)?)?;
+            Ok(grains
+                .into_iter()
+                .map(|grain| Grain {
+                    x: grain.x + left as f32 - TILE_MARGIN as f32,
+                    y: grain.y + top as f32 - TILE_MARGIN as f32,
+                    ..grain
+                })
+                .filter(|grain| {
+                    grain.x >= left as f32 && grain.x < right as f32 && grain.y >= top as f32 && grain.y < bottom as f32
+                })
+                .collect::<Vec<_>>())
+        })
+        .collect::<Result<_, String>>()?;
+    Ok(tiles.concat())
+}
+
+// Stamps for one band, each grain sized by the photo's tone beneath it when there is one
+fn band_stamps(data: &StockData, params: &GrainParams, grid: &TileGrid, ty: u32, photo: Option<&PhotoTones>) -> Result<Vec<GrainStamp>, String> {
+    let mut grains = band_grains(data, params, grid, ty)?;
+    if let Some(photo) = photo {
+        photo.scale_grains(&mut grains);
+    }
+    Ok(raster::grain_stamps(&grains, &data.stock, params))
+}
+
+// Rasterises one band. Grains near its top or bottom edge were generated in the bands
+// above and below, so their stamps are drawn here too.
+fn render_band(grid: &TileGrid, ty: u32, sources: [&[GrainStamp]; 3]) -> Vec<u8> {
+    let band_height = grid.tile_height(ty);
+    let mut band = vec![0u8; grid.width as usize * 4 * band_height as usize];
+    raster::rasterise(sources.into_iter().flatten(), 0, ty * TILE_SIZE, grid.width, band_height, &mut band);
+    band
+}
+
+// Renders the canvas band by band, handing each band's RGBA rows (top to bottom) to
+// `on_band`. Returns the number of grains generated.
+pub fn render_tiled<F>(params: &GrainParams, photo: Option<&PhotoTones>, mut on_band: F) -> Result<usize, String>
+where
+    F: FnMut(&[u8]) -> Result<(), String>,
+{
+    if params.render_mode == RenderMode::Boolean {
+        return boolean_model::render_layer_bands(params, on_band);
+    }
+    let render_start = std::time::Instant::now();
+    let data = StockData::load(&params.film_stock)?;
+    let grid = TileGrid::new(params.width, params.height);
+    println!("🧩 Tiled render: {}x{} in {}x{} tiles of {}px", params.width, params.height, grid.columns, grid.rows, TILE_SIZE);
+
+    let mut grain_count = 0;
+    let mut previous = Vec::new();
+    let mut current = band_stamps(&data, params, &grid, 0, photo)?;
+    for ty in 0..grid.rows {
+        let next = if ty + 1 < grid.rows { band_stamps(&data, params, &grid, ty + 1, photo)? } else { Vec::new() };
+        grain_count += current.len();
+
+        on_band(&render_band(&grid, ty, [&previous, &current, &next]))?;
+        previous = std::mem::replace(&mut current, next);
+    }
+
+    println!("⏱️ Tiled render: {}ms for {} grains", render_start.elapsed().as_millis(), grain_count);
+    Ok(grain_count)
+}
+
+// Tiled render collected into one image, for canvases that still fit in memory as pixels
+pub fn render_tiled_image(params: &GrainParams, photo: Option<&PhotoTones>) -> Result<(RgbaImage, usize), String> {
+    let mut data = Vec::with_capacity(params.width as usize * params.height as usize * 4);
+    let grain_count = render_tiled(params, photo, |band| {
+        data.extend_from_slice(band);
+        Ok(())
+    })?;
+    let image = RgbaImage::from_raw(params.width, params.height, data).ok_or("Tiled render has the wrong size")?;
+    Ok((image, grain_count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(width: u32, height: u32) -> GrainParams {
+        let mut params: GrainParams = serde_json::from_str(concat!(
+            r#"{"film_stock":"Kodak Tri-X 400","exposure_compensation":0,"size_multiplier":1,"contrast":100,"#,
+            r#""grain_density":1000,"width":0,"height":0,"background":"transparent","seed":11,"scan_dpi":2400}"#
+        ))
+        .unwrap();
+        (params.width, params.height) = (width, height);
+        params
+    }
+
+    #[test]
+    fn grains_across_a_band_seam_reach_both_bands() {
+        let params = params(300, 2 * TILE_SIZE + 100);
+        let data = StockData::load(&params.film_stock).unwrap();
+        let grid = TileGrid::new(params.width, params.height);
+
+        // Each band keeps only the grains centred in it, right up to the seam
+        let seam = TILE_SIZE as f32;
+        let (above, below) = (band_grains(&data, &params, &grid, 0).unwrap(), band_grains(&data, &params, &grid, 1).unwrap());
+        assert!(above.iter().all(|grain| grain.y >= 0.0 && grain.y < seam));
+        assert!(below.iter().all(|grain| grain.y >= seam && grain.y < 2.0 * seam));
+        assert!(above.iter().any(|grain| grain.y > seam - 2.0) && below.iter().any(|grain| grain.y < seam + 2.0));
+
+        // A grain generated above the seam is drawn into the band below as well
+        let grain = Grain { x: 100.0, y: seam - 0.5, size: 6.0, opacity: 1.0, shape_factor: 1.0, angle: 0.0, dye_layer: None };
+        let stamps = raster::grain_stamps(&[grain], &data.stock, &params);
+        let alpha = |band: &[u8], row: u32| band[(row * params.width + 100) as usize * 4 + 3];
+        let (upper, lower) = (render_band(&grid, 0, [&[], &stamps, &[]]), render_band(&grid, 1, [&stamps, &[], &[]]));
+        assert!(alpha(&upper, TILE_SIZE - 1) > 0 && alpha(&lower, 0) > 0 && alpha(&lower, 2) > 0);
+
+        // And the whole tiled render is every band's grains drawn at once
+        let (tiled, _) = render_tiled_image(&params, None).unwrap();
+        let all: Vec<GrainStamp> = (0..grid.rows).flat_map(|ty| band_stamps(&data, &params, &grid, ty, None).unwrap()).collect();
+        let mut whole = vec![0u8; (params.width * params.height * 4) as usize];
+        raster::rasterise(&all, 0, 0, params.width, params.height, &mut whole);
+        assert!(tiled.as_raw() == &whole);
+    }
+
+    #[test]
+    fn tiles_keep_the_untiled_grain_density() {
+        let params = params(1200, 1100);
+        let data = StockData::load(&params.film_stock).unwrap();
+        let untiled = generate_stock_grains(&data, &params).unwrap().len() as f32;
+        let tiled = render_tiled(&params, None, |_| Ok(())).unwrap() as f32;
+        assert!((tiled / untiled - 1.0).abs() < 0.05, "{} tiled vs {} untiled", tiled, untiled);
+    }
+}